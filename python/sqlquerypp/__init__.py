@@ -1,9 +1,23 @@
-from .compiler import Compiler, MySQL84Compiler
-from .types import Query
+from .compiler import (
+    Compiler,
+    MariaDBCompiler,
+    MySQL80Compiler,
+    MySQL84Compiler,
+    PostgreSQLCompiler,
+    SQLiteCompiler,
+)
+from .sqlquerypp import QueryCompilerError
+from .types import PlaceholderStyle, Query
 
 # public API
 __all__ = [
     "Compiler",
+    "MariaDBCompiler",
+    "MySQL80Compiler",
     "MySQL84Compiler",
+    "PlaceholderStyle",
+    "PostgreSQLCompiler",
     "Query",
+    "QueryCompilerError",
+    "SQLiteCompiler",
 ]
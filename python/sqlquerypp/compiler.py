@@ -1,23 +1,64 @@
-from abc import ABC, abstractmethod
+from abc import ABC
 import hashlib
 import re
-from typing import Any, Sequence
+from typing import Any, Mapping, Optional, Sequence, Union
 
 from .sqlquerypp import (
     CompiledQueryDescriptor,
-    preprocess_mysql84_query,
+    preprocess_query,
+    preprocess_script,
 )
-from .types import Query
+from .types import PlaceholderStyle, Query
 
 
 class Compiler(ABC):
-    @abstractmethod
+    # the name of the target dialect, e.g. "mysql84"
+    dialect: str
+    # the declared version of the target server, if the dialect depends on it
+    server_version: Optional[str] = None
+
     def _compile_template(self, statement: str) -> CompiledQueryDescriptor:
-        pass
+        return preprocess_query(
+            statement,
+            self.dialect,
+            self.server_version,
+            self.placeholder_style,
+            self.output_placeholder_style,
+            self.pretty_print,
+        )
+
+    def _compile_script(self, script: str) -> list[CompiledQueryDescriptor]:
+        return preprocess_script(
+            script,
+            self.dialect,
+            self.server_version,
+            self.placeholder_style,
+            self.output_placeholder_style,
+            self.pretty_print,
+        )
 
-    def __init__(self, variable_placeholder: str = "?") -> None:
+    def __init__(
+        self,
+        placeholder_style: PlaceholderStyle = "qmark",
+        output_placeholder_style: Optional[PlaceholderStyle] = None,
+        pretty_print: bool = False,
+    ) -> None:
+        """
+        Placeholders of templates are expected in `placeholder_style`.
+        Placeholders of compiled queries are rewritten to
+        `output_placeholder_style`, defaulting to `placeholder_style`.
+
+        Compiled queries keep everything outside of directives (including
+        comments like optimizer hints) as is, unless `pretty_print` is
+        enabled, which reformats them as a whole.
+        """
+        self.pretty_print = pretty_print
+        self.placeholder_style = placeholder_style
+        self.output_placeholder_style = (
+            output_placeholder_style or placeholder_style
+        )
         self._cache: dict[str, CompiledQueryDescriptor] = {}
-        self._variable_placeholder = variable_placeholder
+        self._script_cache: dict[str, list[CompiledQueryDescriptor]] = {}
 
     def compile(self, template: Query) -> Query:
         """
@@ -29,43 +70,44 @@ class Compiler(ABC):
         )
         return Query(statement=descriptor.statement, parameters=parameters)
 
+    def compile_script(self, template: Query) -> list[Query]:
+        """
+        Compiles each `;`-separated statement of a given script to valid SQL.
+
+        The parameters of the script are shared by all of its statements,
+        i.e. positional parameters are given in order of their placeholders
+        within the entire script. Each compiled query only receives the
+        parameters of its own statement.
+        """
+        key = self._build_cache_key(template.statement)
+        if key not in self._script_cache:
+            self._script_cache[key] = self._compile_script(template.statement)
+        return [
+            Query(
+                statement=descriptor.statement,
+                parameters=self._resolve_parameters_from_descriptor(
+                    template, descriptor
+                ),
+            )
+            for descriptor in self._script_cache[key]
+        ]
+
     def _resolve_parameters_from_descriptor(
         self,
         template: Query,
         descriptor: CompiledQueryDescriptor,
-    ) -> Sequence[Any]:
-        final_parameters: list[Any] = []
-
-        last_statement_offset = 0
-        last_parameters_offset = 0
-        for slice in descriptor.combined_result_node_slices:
-            parameters_outside_combined_result = template.statement[
-                last_statement_offset : slice.scope_begin
-            ].count(self._variable_placeholder)
-            parameters_within_combined_result = template.statement[
-                slice.scope_begin : slice.scope_end
-            ].count(self._variable_placeholder)
-
-            final_parameters += template.parameters[
-                last_parameters_offset : last_parameters_offset
-                + parameters_outside_combined_result
-            ]
-            last_parameters_offset += parameters_outside_combined_result
-
-            # compiler needs to duplicate params within combined_result nodes.
-            # that's why we append them twice, but each in order.
-            for _ in range(2):
-                final_parameters += template.parameters[
-                    last_parameters_offset : last_parameters_offset
-                    + parameters_within_combined_result
-                ]
-            last_parameters_offset += parameters_within_combined_result
-            last_statement_offset = slice.scope_end
-
-        if last_statement_offset < len(template.statement):
-            final_parameters += template.parameters[last_parameters_offset:]
-
-        return final_parameters
+    ) -> Union[Sequence[Any], Mapping[str, Any]]:
+        # the compiler determines which template parameter each placeholder
+        # of the compiled statement binds (e.g. parameters of a
+        # combined_result node are bound multiple times).
+        if isinstance(template.parameters, Mapping):
+            if self.output_placeholder_style in ("named", "pyformat"):
+                # placeholders are still bound by name
+                return template.parameters
+            return descriptor.expand_named_parameters(
+                dict(template.parameters)
+            )
+        return [template.parameters[i] for i in descriptor.parameter_order]
 
     def _resolve_compiled_descriptor(
         self, statement: str
@@ -76,7 +118,13 @@ class Compiler(ABC):
         return self._cache[key]
 
     def _build_cache_key(self, statement: str) -> str:
-        normalized = self._get_normalized_query_template_string(statement)
+        # unless pretty printed, compiled queries keep the whitespace of
+        # their templates
+        normalized = (
+            self._get_normalized_query_template_string(statement)
+            if self.pretty_print
+            else statement.encode()
+        )
         cache_key = hashlib.sha256(normalized).hexdigest()
         return cache_key
 
@@ -98,19 +146,128 @@ class MySQL84Compiler(Compiler):
     queries.
     """
 
-    def _compile_template(self, statement: str) -> CompiledQueryDescriptor:
-        if self.pep_249_placeholders:
-            statement = statement.replace("%s", "?")
+    dialect = "mysql84"
+
+    def __init__(
+        self,
+        pep_249_placeholders: bool = True,
+        placeholder_style: Optional[PlaceholderStyle] = None,
+        output_placeholder_style: Optional[PlaceholderStyle] = None,
+        pretty_print: bool = False,
+    ) -> None:
+        """
+        Templates use `%s` placeholders by default (as most PEP 249 drivers
+        for MySQL do), or `?` placeholders if `pep_249_placeholders` is
+        disabled. Any other `placeholder_style` may be given explicitly.
+        """
+        if placeholder_style is None:
+            placeholder_style = "format" if pep_249_placeholders else "qmark"
+        super().__init__(
+            placeholder_style, output_placeholder_style, pretty_print
+        )
 
-        result = preprocess_mysql84_query(statement)
 
-        if self.pep_249_placeholders:
-            return CompiledQueryDescriptor(
-                statement=result.statement.replace("?", "%s"),
-                combined_result_node_slices=result.combined_result_node_slices,
-            )
-        return result
+class MySQL80Compiler(MySQL84Compiler):
+    """
+    An implementation compiling `sqlquerypp` specific syntax to valid MySQL 8.0
+    queries.
+    """
+
+    dialect = "mysql80"
+
+    def __init__(
+        self,
+        server_version: str = "8.0.11",
+        pep_249_placeholders: bool = True,
+        placeholder_style: Optional[PlaceholderStyle] = None,
+        output_placeholder_style: Optional[PlaceholderStyle] = None,
+        pretty_print: bool = False,
+    ) -> None:
+        """
+        `server_version` declares the version of the target server, e.g.
+        `"8.0.35"`. Compiling raises `QueryCompilerError` if a directive
+        can't be expressed on that version.
+        """
+        self.server_version = server_version
+        super().__init__(
+            pep_249_placeholders,
+            placeholder_style,
+            output_placeholder_style,
+            pretty_print,
+        )
+
+
+class MariaDBCompiler(MySQL84Compiler):
+    """
+    An implementation compiling `sqlquerypp` specific syntax to valid MariaDB
+    queries.
+    """
+
+    dialect = "mariadb"
+
+    def __init__(
+        self,
+        server_version: str = "10.11.2",
+        pep_249_placeholders: bool = True,
+        placeholder_style: Optional[PlaceholderStyle] = None,
+        output_placeholder_style: Optional[PlaceholderStyle] = None,
+        pretty_print: bool = False,
+    ) -> None:
+        """
+        `server_version` declares the version of the target server, e.g.
+        `"10.11.6"`. Compiling raises `QueryCompilerError` if a directive
+        can't be expressed on that version.
+        """
+        self.server_version = server_version
+        super().__init__(
+            pep_249_placeholders,
+            placeholder_style,
+            output_placeholder_style,
+            pretty_print,
+        )
+
+
+class PostgreSQLCompiler(Compiler):
+    """
+    An implementation compiling `sqlquerypp` specific syntax to valid
+    PostgreSQL queries, based on `LATERAL` joins.
+    """
 
-    def __init__(self, pep_249_placeholders: bool = True) -> None:
-        self.pep_249_placeholders = pep_249_placeholders
-        super().__init__("%s" if self.pep_249_placeholders else "?")
+    dialect = "postgres"
+
+    def __init__(
+        self,
+        placeholder_style: PlaceholderStyle = "format",
+        output_placeholder_style: PlaceholderStyle = "numeric",
+        pretty_print: bool = False,
+    ) -> None:
+        """
+        Templates use `%s` placeholders by default, while compiled queries use
+        PostgreSQL's native `$1`, `$2`, ... placeholders.
+        """
+        super().__init__(
+            placeholder_style, output_placeholder_style, pretty_print
+        )
+
+
+class SQLiteCompiler(Compiler):
+    """
+    An implementation compiling `sqlquerypp` specific syntax to valid SQLite
+    queries, e.g. for running the same templates against an in-memory
+    database within tests.
+    """
+
+    dialect = "sqlite"
+
+    def __init__(
+        self,
+        placeholder_style: PlaceholderStyle = "qmark",
+        output_placeholder_style: Optional[PlaceholderStyle] = None,
+        pretty_print: bool = False,
+    ) -> None:
+        """
+        Templates use `?` placeholders by default, as `sqlite3` does.
+        """
+        super().__init__(
+            placeholder_style, output_placeholder_style, pretty_print
+        )
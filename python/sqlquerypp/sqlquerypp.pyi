@@ -1,3 +1,12 @@
+from typing import Any
+
+class QueryCompilerError(ValueError):
+    line: int
+    column: int
+    snippet: str
+    help: str | None
+    errors: list["QueryCompilerError"]
+
 class CombinedResultNodeSlice:
     scope_begin: int
     scope_end: int
@@ -5,11 +14,34 @@ class CombinedResultNodeSlice:
 class CompiledQueryDescriptor:
     statement: str
     combined_result_node_slices: list[CombinedResultNodeSlice]
+    parameter_order: list[int]
+    parameter_positions: dict[str, list[int]]
 
     def __init__(
         self,
         statement: str,
         combined_result_node_slices: list[CombinedResultNodeSlice],
+        parameter_order: list[int],
+        parameter_positions: dict[str, list[int]] = {},
     ): ...
+    def expand_named_parameters(
+        self, parameters: dict[str, Any]
+    ) -> list[Any]: ...
+
+def preprocess_query(
+    statement: str,
+    dialect: str,
+    server_version: str | None = None,
+    placeholder_style: str = "qmark",
+    output_placeholder_style: str | None = None,
+    pretty_print: bool = False,
+) -> CompiledQueryDescriptor: ...
 
-def preprocess_mysql84_query(statement: str) -> CompiledQueryDescriptor: ...
+def preprocess_script(
+    script: str,
+    dialect: str,
+    server_version: str | None = None,
+    placeholder_style: str = "qmark",
+    output_placeholder_style: str | None = None,
+    pretty_print: bool = False,
+) -> list[CompiledQueryDescriptor]: ...
@@ -1,8 +1,12 @@
 from dataclasses import dataclass
-from typing import Any, Sequence
+from typing import Any, Literal, Mapping, Sequence, Union
+
+# placeholder styles as defined by PEP 249 (`paramstyle`)
+PlaceholderStyle = Literal["qmark", "format", "numeric", "named", "pyformat"]
 
 
 @dataclass(frozen=True)
 class Query:
     statement: str
-    parameters: Sequence[Any]
+    # a mapping is supported by templates with named placeholders only
+    parameters: Union[Sequence[Any], Mapping[str, Any]]
@@ -0,0 +1,105 @@
+(WITH RECURSIVE all_entries (loop_value, loop_match, col_a1, col_a2, col_b1, col_b2) AS (
+  WITH loop_values (loop_value) AS (
+    SELECT DISTINCT
+      *
+    FROM
+      (
+        SELECT
+          col_a1
+        FROM
+          table_a
+        WHERE
+          criteria = %s
+      ) AS loop_values
+  )
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.col_a1,
+    a.col_a2,
+    b.col_b1,
+    b.col_b2
+  FROM
+    loop_values AS loop_key
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond1 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
+  WHERE
+    loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values) AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
+  UNION ALL
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.col_a1,
+    a.col_a2,
+    b.col_b1,
+    b.col_b2
+  FROM
+    all_entries
+    INNER JOIN loop_values AS loop_key ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values WHERE loop_value > all_entries.loop_value)
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond1 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
+  WHERE
+    all_entries.loop_match = 0 AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
+)
+SELECT
+  col_a1,
+  col_a2,
+  col_b1,
+  col_b2
+FROM
+  all_entries
+WHERE
+  loop_match = 1)
+UNION ALL
+(WITH RECURSIVE all_entries (loop_value, loop_match, col_a1, col_a2, col_b1, col_b2) AS (
+  WITH loop_values (loop_value) AS (
+    SELECT DISTINCT
+      *
+    FROM
+      (
+        SELECT
+          col_a1
+        FROM
+          table_a
+        WHERE
+          criteria = %s
+      ) AS loop_values
+  )
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.col_a1,
+    a.col_a2,
+    b.col_b1,
+    b.col_b2
+  FROM
+    loop_values AS loop_key
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond2 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
+  WHERE
+    loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values) AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
+  UNION ALL
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.col_a1,
+    a.col_a2,
+    b.col_b1,
+    b.col_b2
+  FROM
+    all_entries
+    INNER JOIN loop_values AS loop_key ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values WHERE loop_value > all_entries.loop_value)
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond2 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
+  WHERE
+    all_entries.loop_match = 0 AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
+)
+SELECT
+  col_a1,
+  col_a2,
+  col_b1,
+  col_b2
+FROM
+  all_entries
+WHERE
+  loop_match = 1)
\ No newline at end of file
@@ -0,0 +1,93 @@
+(WITH RECURSIVE all_entries (loop_value, loop_match, id, x) AS (
+  WITH loop_values (loop_value) AS (
+    SELECT DISTINCT
+      *
+    FROM
+      (
+        SELECT
+          id
+        FROM
+          a
+        WHERE
+          criteria = %s
+      ) AS loop_values
+  )
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.id,
+    b.x
+  FROM
+    loop_values AS loop_key
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (a INNER JOIN b ON b.a_id = a.id AND b.cond = %s) ON loop_matches.loop_match = 1 AND a.id = loop_key.loop_value
+  WHERE
+    loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values) AND (loop_matches.loop_match = 0 OR a.id = loop_key.loop_value)
+  UNION ALL
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.id,
+    b.x
+  FROM
+    all_entries
+    INNER JOIN loop_values AS loop_key ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values WHERE loop_value > all_entries.loop_value)
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (a INNER JOIN b ON b.a_id = a.id AND b.cond = %s) ON loop_matches.loop_match = 1 AND a.id = loop_key.loop_value
+  WHERE
+    all_entries.loop_match = 0 AND (loop_matches.loop_match = 0 OR a.id = loop_key.loop_value)
+)
+SELECT
+  id,
+  x
+FROM
+  all_entries
+WHERE
+  loop_match = 1)
+UNION ALL
+(WITH RECURSIVE all_entries (loop_value, loop_match, id, x) AS (
+  WITH loop_values (loop_value) AS (
+    SELECT DISTINCT
+      *
+    FROM
+      (
+        SELECT
+          id
+        FROM
+          a
+        WHERE
+          criteria = %s
+      ) AS loop_values
+  )
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.id,
+    b.x
+  FROM
+    loop_values AS loop_key
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (a INNER JOIN b ON b.a_id = a.id AND b.cond = %s) ON loop_matches.loop_match = 1 AND a.id = loop_key.loop_value
+  WHERE
+    loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values) AND (loop_matches.loop_match = 0 OR a.id = loop_key.loop_value)
+  UNION ALL
+  SELECT
+    loop_key.loop_value,
+    loop_matches.loop_match,
+    a.id,
+    b.x
+  FROM
+    all_entries
+    INNER JOIN loop_values AS loop_key ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values WHERE loop_value > all_entries.loop_value)
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (a INNER JOIN b ON b.a_id = a.id AND b.cond = %s) ON loop_matches.loop_match = 1 AND a.id = loop_key.loop_value
+  WHERE
+    all_entries.loop_match = 0 AND (loop_matches.loop_match = 0 OR a.id = loop_key.loop_value)
+)
+SELECT
+  id,
+  x
+FROM
+  all_entries
+WHERE
+  loop_match = 1)
\ No newline at end of file
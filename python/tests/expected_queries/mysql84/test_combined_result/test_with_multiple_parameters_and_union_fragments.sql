@@ -1,36 +1,45 @@
-(WITH RECURSIVE all_entries (n, col_a1, col_a2, col_b1, col_b2) AS (
-  WITH loop_values AS (
-    SELECT
-      col_a1
+(WITH RECURSIVE all_entries (loop_value, loop_match, col_a1, col_a2, col_b1, col_b2) AS (
+  WITH loop_values (loop_value) AS (
+    SELECT DISTINCT
+      *
     FROM
-      table_a
-    WHERE
-      criteria = %s
+      (
+        SELECT
+          col_a1
+        FROM
+          table_a
+        WHERE
+          criteria = %s
+      ) AS loop_values
   )
   SELECT
-    0,
+    loop_key.loop_value,
+    loop_matches.loop_match,
     a.col_a1,
     a.col_a2,
     b.col_b1,
     b.col_b2
   FROM
-    table_a AS a
-    LEFT JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond1 = %s AND b.cond2 = %s
+    loop_values AS loop_key
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond1 = %s AND b.cond2 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
   WHERE
-    a.col_a1 = (SELECT * FROM loop_values LIMIT 1)
+    loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values) AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
   UNION ALL
   SELECT
-    n + 1,
+    loop_key.loop_value,
+    loop_matches.loop_match,
     a.col_a1,
     a.col_a2,
     b.col_b1,
     b.col_b2
   FROM
     all_entries
-    LEFT JOIN table_a AS a ON a.col_a1 = (SELECT col_a1 FROM loop_values WHERE col_a1 > all_entries.col_a1 LIMIT 1)
-    LEFT JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond1 = %s AND b.cond2 = %s
+    INNER JOIN loop_values AS loop_key ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values WHERE loop_value > all_entries.loop_value)
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond1 = %s AND b.cond2 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
   WHERE
-    n + 1 < (SELECT COUNT(*) FROM loop_values)
+    all_entries.loop_match = 0 AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
 )
 SELECT
   col_a1,
@@ -40,41 +49,50 @@ SELECT
 FROM
   all_entries
 WHERE
-  col_b1 IS NOT NULL AND col_b2 IS NOT NULL)
+  loop_match = 1)
 UNION ALL
-(WITH RECURSIVE all_entries (n, col_a1, col_a2, col_b1, col_b2) AS (
-  WITH loop_values AS (
-    SELECT
-      col_a1
+(WITH RECURSIVE all_entries (loop_value, loop_match, col_a1, col_a2, col_b1, col_b2) AS (
+  WITH loop_values (loop_value) AS (
+    SELECT DISTINCT
+      *
     FROM
-      table_a
-    WHERE
-      criteria = %s
+      (
+        SELECT
+          col_a1
+        FROM
+          table_a
+        WHERE
+          criteria = %s
+      ) AS loop_values
   )
   SELECT
-    0,
+    loop_key.loop_value,
+    loop_matches.loop_match,
     a.col_a1,
     a.col_a2,
     b.col_b1,
     b.col_b2
   FROM
-    table_a AS a
-    LEFT JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond3 = %s AND b.cond4 = %s
+    loop_values AS loop_key
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond3 = %s AND b.cond4 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
   WHERE
-    a.col_a1 = (SELECT * FROM loop_values LIMIT 1)
+    loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values) AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
   UNION ALL
   SELECT
-    n + 1,
+    loop_key.loop_value,
+    loop_matches.loop_match,
     a.col_a1,
     a.col_a2,
     b.col_b1,
     b.col_b2
   FROM
     all_entries
-    LEFT JOIN table_a AS a ON a.col_a1 = (SELECT col_a1 FROM loop_values WHERE col_a1 > all_entries.col_a1 LIMIT 1)
-    LEFT JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond3 = %s AND b.cond4 = %s
+    INNER JOIN loop_values AS loop_key ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values WHERE loop_value > all_entries.loop_value)
+    CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+    LEFT JOIN (table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond3 = %s AND b.cond4 = %s) ON loop_matches.loop_match = 1 AND a.col_a1 = loop_key.loop_value
   WHERE
-    n + 1 < (SELECT COUNT(*) FROM loop_values)
+    all_entries.loop_match = 0 AND (loop_matches.loop_match = 0 OR a.col_a1 = loop_key.loop_value)
 )
 SELECT
   col_a1,
@@ -84,4 +102,4 @@ SELECT
 FROM
   all_entries
 WHERE
-  col_b1 IS NOT NULL AND col_b2 IS NOT NULL)
\ No newline at end of file
+  loop_match = 1)
\ No newline at end of file
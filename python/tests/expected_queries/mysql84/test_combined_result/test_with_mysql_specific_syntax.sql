@@ -0,0 +1,6 @@
+SELECT
+  `c`.`id`,
+  x.`sku`
+FROM
+  customer AS c USE INDEX (PRIMARY)
+  STRAIGHT_JOIN ((WITH RECURSIVE all_entries (loop_value, loop_match, `id`, `customer_id`, `sku`) AS (WITH loop_values (loop_value) AS (SELECT DISTINCT * FROM (SELECT `id` FROM `orders` WHERE created > NOW() - INTERVAL 7 DAY) AS loop_values) SELECT loop_key.loop_value, loop_matches.loop_match, `o`.`id`, `o`.`customer_id`, i.`sku` FROM loop_values AS loop_key CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches LEFT JOIN (`orders` AS `o` INNER JOIN order_item AS i ON i.order_id = `o`.`id`) ON loop_matches.loop_match = 1 AND `o`.`id` = loop_key.loop_value WHERE loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values) AND (loop_matches.loop_match = 0 OR `o`.`id` = loop_key.loop_value) UNION ALL SELECT loop_key.loop_value, loop_matches.loop_match, `o`.`id`, `o`.`customer_id`, i.`sku` FROM all_entries INNER JOIN loop_values AS loop_key ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values WHERE loop_value > all_entries.loop_value) CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches LEFT JOIN (`orders` AS `o` INNER JOIN order_item AS i ON i.order_id = `o`.`id`) ON loop_matches.loop_match = 1 AND `o`.`id` = loop_key.loop_value WHERE all_entries.loop_match = 0 AND (loop_matches.loop_match = 0 OR `o`.`id` = loop_key.loop_value)) SELECT `id`, `customer_id`, `sku` FROM all_entries WHERE loop_match = 1)) AS x ON x.customer_id = `c`.`id` LOCK IN SHARE MODE
\ No newline at end of file
@@ -0,0 +1,39 @@
+(SELECT
+  all_entries.*
+FROM
+  (
+    SELECT DISTINCT
+      *
+    FROM
+      (
+        SELECT
+          col_a1
+        FROM
+          table_a
+        WHERE
+          criteria = $1
+      ) AS loop_values (loop_value)
+    WHERE
+      loop_value IS NOT NULL
+  ) AS loop_values
+  CROSS JOIN LATERAL (SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2 FROM table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond1 = $2 WHERE a.col_a1 = loop_values.loop_value) AS all_entries)
+UNION ALL
+(SELECT
+  all_entries.*
+FROM
+  (
+    SELECT DISTINCT
+      *
+    FROM
+      (
+        SELECT
+          col_a1
+        FROM
+          table_a
+        WHERE
+          criteria = $3
+      ) AS loop_values (loop_value)
+    WHERE
+      loop_value IS NOT NULL
+  ) AS loop_values
+  CROSS JOIN LATERAL (SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2 FROM table_a AS a INNER JOIN table_b AS b ON b.col_a1 = a.col_a1 AND b.cond2 = $4 WHERE a.col_a1 = loop_values.loop_value) AS all_entries)
\ No newline at end of file
@@ -0,0 +1,19 @@
+(SELECT
+  all_entries.*
+FROM
+  (
+    SELECT DISTINCT
+      *
+    FROM
+      (
+        SELECT
+          id
+        FROM
+          customer
+        WHERE
+          region = $1
+      ) AS loop_values (loop_value)
+    WHERE
+      loop_value IS NOT NULL
+  ) AS loop_values
+  CROSS JOIN LATERAL (SELECT c.id, x.order_id, x.sku FROM customer AS c INNER JOIN ((SELECT all_entries_1.* FROM (SELECT DISTINCT * FROM (SELECT id FROM orders WHERE state = $2) AS loop_values_1 (loop_value) WHERE loop_value IS NOT NULL) AS loop_values_1 CROSS JOIN LATERAL (SELECT o.id, o.customer_id, i.order_id, i.sku FROM orders AS o INNER JOIN order_item AS i ON i.order_id = o.id AND i.sku <> $3 WHERE o.id = loop_values_1.loop_value) AS all_entries_1)) AS x ON x.customer_id = c.id WHERE c.id = loop_values.loop_value AND c.label <> '$cid') AS all_entries)
\ No newline at end of file
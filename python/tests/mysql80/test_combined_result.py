@@ -0,0 +1,60 @@
+from sqlquerypp import Compiler, MySQL80Compiler, Query, QueryCompilerError
+
+from ..common import CompilerTestCase
+
+
+class CombinedResultTests(CompilerTestCase):
+    def _get_compiler(self) -> Compiler:
+        return MySQL80Compiler(pretty_print=True)
+
+    def test_with_multiple_parameters_and_union_fragments(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT col_a1 FROM table_a
+                             WHERE criteria = %s) AS $id {
+                SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2
+                FROM table_a a
+                INNER JOIN table_b b
+                ON b.col_a1 = a.col_a1
+                AND b.cond1 = %s
+                WHERE a.col_a1 = $id
+            }
+            UNION ALL
+            combined_result (SELECT col_a1 FROM table_a
+                             WHERE criteria = %s) AS $id {
+                SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2
+                FROM table_a a
+                INNER JOIN table_b b
+                ON b.col_a1 = a.col_a1
+                AND b.cond2 = %s
+                WHERE a.col_a1 = $id
+            }
+            """,
+            ["CRIT1", 1337, "CRIT2", 31415],
+        )
+        expected = Query(
+            self.loadQueryFromFile(
+                __name__,
+                "test_with_multiple_parameters_and_union_fragments",
+            ),
+            ["CRIT1", 1337, 1337, "CRIT2", 31415, 31415],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_recursive_cte_requires_newer_server_version(self) -> None:
+        template = Query(
+            "combined_result (SELECT id FROM a) AS $id {\n"
+            "    SELECT a.id, b.x FROM a\n"
+            "    INNER JOIN b ON b.a_id = a.id\n"
+            "    WHERE a.id = $id\n"
+            "}",
+            [],
+        )
+        with self.assertRaises(QueryCompilerError) as context:
+            MySQL80Compiler(server_version="8.0.0").compile(template)
+
+        error = context.exception
+        self.assertEqual(1, error.line)
+        self.assertEqual(1, error.column)
+        self.assertIn("require version 8.0.1 or later", error.help)
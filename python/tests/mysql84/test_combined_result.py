@@ -5,7 +5,7 @@ from ..common import CompilerTestCase
 
 class CombinedResultTests(CompilerTestCase):
     def _get_compiler(self) -> Compiler:
-        return MySQL84Compiler()
+        return MySQL84Compiler(pretty_print=True)
 
     def test_with_multiple_parameters_and_union_fragments(self) -> None:
         template = Query(
@@ -43,3 +43,165 @@ class CombinedResultTests(CompilerTestCase):
         )
 
         self.assertGeneratedQueryEqual(expected, template)
+
+    def test_with_braces_and_json_paths_in_literals(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM a
+                             WHERE label <> '{' AND criteria = %s) AS $id {
+                -- braces like } in comments are ignored as well
+                SELECT a.id, b.x
+                FROM a
+                INNER JOIN b
+                ON b.a_id = a.id
+                AND JSON_EXTRACT(b.doc, '$.items[0]') <> '}'
+                WHERE a.id = $id
+            }
+            """,
+            ["CRIT"],
+        )
+        expected = Query(
+            self.loadQueryFromFile(
+                __name__,
+                "test_with_braces_and_json_paths_in_literals",
+            ),
+            ["CRIT"],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_with_nested_combined_result(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM customer) AS $cid {
+                SELECT c.id, x.order_id, x.sku
+                FROM customer c
+                INNER JOIN (
+                    combined_result (SELECT id FROM orders) AS $oid {
+                        SELECT o.id, o.customer_id, i.order_id, i.sku
+                        FROM orders o
+                        INNER JOIN order_item i ON i.order_id = o.id
+                        WHERE o.id = $oid
+                    }
+                ) x ON x.customer_id = c.id
+                WHERE c.id = $cid
+            }
+            """,
+            [],
+        )
+        expected = Query(
+            self.loadQueryFromFile(
+                __name__,
+                "test_with_nested_combined_result",
+            ),
+            [],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_with_parameters_in_nested_combined_result(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM customer WHERE region = %s) AS $cid {
+                SELECT c.id, x.order_id, x.sku
+                FROM customer c
+                INNER JOIN (
+                    combined_result (SELECT id FROM orders
+                                     WHERE state = %s) AS $oid {
+                        SELECT o.id, o.customer_id, i.order_id, i.sku
+                        FROM orders o
+                        INNER JOIN order_item i
+                        ON i.order_id = o.id AND i.sku <> %s
+                        WHERE o.id = $oid
+                    }
+                ) x ON x.customer_id = c.id
+                WHERE c.id = $cid
+            }
+            """,
+            ["REGION", "STATE", "SKU"],
+        )
+        expected = Query(
+            self.loadQueryFromFile(
+                __name__,
+                "test_with_parameters_in_nested_combined_result",
+            ),
+            ["REGION", "STATE", "SKU", "SKU", "STATE", "SKU", "SKU"],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_with_identical_combined_results(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM a WHERE criteria = %s) AS $id {
+                SELECT a.id, b.x FROM a
+                INNER JOIN b ON b.a_id = a.id AND b.cond = %s
+                WHERE a.id = $id
+            }
+            UNION ALL
+            combined_result (SELECT id FROM a WHERE criteria = %s) AS $id {
+                SELECT a.id, b.x FROM a
+                INNER JOIN b ON b.a_id = a.id AND b.cond = %s
+                WHERE a.id = $id
+            }
+            """,
+            ["CRIT1", 1, "CRIT2", 2],
+        )
+        expected = Query(
+            self.loadQueryFromFile(
+                __name__,
+                "test_with_identical_combined_results",
+            ),
+            ["CRIT1", 1, 1, "CRIT2", 2, 2],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_with_mysql_specific_syntax(self) -> None:
+        template = Query(
+            """
+            SELECT `c`.`id`, x.`sku`
+            FROM customer c USE INDEX (PRIMARY)
+            STRAIGHT_JOIN (
+                combined_result (SELECT `id` FROM `orders`
+                                 WHERE created > NOW() - INTERVAL 7 DAY) AS $id {
+                    SELECT `o`.`id`, `o`.`customer_id`, i.`sku`
+                    FROM `orders` `o`
+                    INNER JOIN order_item i ON i.order_id = `o`.`id`
+                    WHERE `o`.`id` = $id
+                }
+            ) x ON x.customer_id = `c`.`id`
+            LOCK IN SHARE MODE
+            """,
+            [],
+        )
+        expected = Query(
+            self.loadQueryFromFile(__name__, "test_with_mysql_specific_syntax"),
+            [],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_template_is_kept_outside_directives(self) -> None:
+        head = """
+            SELECT /*+ NO_MERGE(x) JOIN_ORDER(c, x) */ c.id, x.sku
+            FROM customer c  -- hand-tuned
+            INNER JOIN (
+        """
+        tail = """
+            ) x ON x.customer_id = c.id
+        """
+        directive = """
+                combined_result (SELECT id FROM orders) AS $id {
+                    SELECT o.id, o.customer_id, i.sku
+                    FROM orders o
+                    INNER JOIN order_item i ON i.order_id = o.id
+                    WHERE o.id = $id
+                }
+        """
+        template = Query(head + directive + tail, [])
+
+        compiled = MySQL84Compiler().compile(template)
+        self.assertTrue(compiled.statement.startswith(head))
+        self.assertTrue(compiled.statement.endswith(tail))
+        self.assertIn("WITH RECURSIVE", compiled.statement)
@@ -0,0 +1,70 @@
+from unittest import TestCase
+
+from sqlquerypp import MySQL84Compiler, Query, QueryCompilerError
+
+
+class DiagnosticsTests(TestCase):
+    def test_unbalanced_parenthese_is_located(self) -> None:
+        template = Query(
+            "SELECT *\n"
+            "FROM (combined_result (SELECT id FROM a AS $id {\n"
+            "    SELECT a.id FROM a WHERE a.id = $id\n"
+            "})",
+            [],
+        )
+        with self.assertRaises(QueryCompilerError) as context:
+            MySQL84Compiler().compile(template)
+
+        error = context.exception
+        self.assertIsInstance(error, ValueError)
+        self.assertEqual(2, error.line)
+        self.assertEqual(23, error.column)
+        self.assertEqual(
+            "add the missing `)` before the `{` of the directive", error.help
+        )
+        self.assertIn(
+            "2 | FROM (combined_result (SELECT id FROM a AS $id {\n"
+            "  |                       ^",
+            error.snippet,
+        )
+        self.assertEqual(error.snippet, str(error))
+
+    def test_invalid_inner_query_points_at_directive_body(self) -> None:
+        template = Query(
+            "combined_result (SELECT id FROM a) AS $id {\n"
+            "    SELECT a.id FROM a\n"
+            "}",
+            [],
+        )
+        with self.assertRaises(QueryCompilerError) as context:
+            MySQL84Compiler().compile(template)
+
+        error = context.exception
+        self.assertEqual(1, error.line)
+        self.assertEqual(43, error.column)
+        self.assertIsNone(error.help)
+        self.assertIn("parsing inner query failed", error.snippet)
+
+    def test_all_errors_are_reported(self) -> None:
+        template = Query(
+            "combined_result (SELECT id FROM a AS $a { SELECT 1 }\n"
+            "UNION ALL\n"
+            "combined_result (SELECT id FROM b) AS $b\n"
+            "UNION ALL\n"
+            "combined_result (SELECT id FROM c) AS $c {\n"
+            "    SELECT c.id FROM c WHERE c.id = $c\n"
+            "}",
+            [],
+        )
+        with self.assertRaises(QueryCompilerError) as context:
+            MySQL84Compiler().compile(template)
+
+        error = context.exception
+        self.assertEqual(
+            [(1, 17), (3, 1)],
+            [(e.line, e.column) for e in error.errors],
+        )
+        self.assertEqual((1, 17), (error.line, error.column))
+        self.assertIn("has an unclosed parenthese", error.snippet)
+        self.assertIn("is incomplete", error.snippet)
+        self.assertEqual([], error.errors[0].errors)
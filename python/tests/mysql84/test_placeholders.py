@@ -0,0 +1,93 @@
+from unittest import TestCase
+
+from sqlquerypp import MySQL84Compiler, Query, QueryCompilerError
+
+
+class PlaceholderTests(TestCase):
+    template = """
+        combined_result (SELECT id FROM a WHERE criteria = {0}) AS $id {{
+            SELECT a.id, b.x, '{0}' AS label
+            FROM a
+            INNER JOIN b ON b.a_id = a.id AND b.cond = {1}
+            WHERE a.id = $id
+        }}
+    """
+
+    def test_placeholders_within_literals_are_preserved(self) -> None:
+        for style, placeholder in [("format", "%s"), ("qmark", "?")]:
+            compiler = MySQL84Compiler(placeholder_style=style)
+            statement = self.template.format(placeholder, placeholder)
+            compiled = compiler.compile(Query(statement, ["CRIT", 42]))
+
+            self.assertIn(f"'{placeholder}' AS label", compiled.statement)
+            self.assertEqual(["CRIT", 42, 42], compiled.parameters)
+
+    def test_qmark_placeholders_without_pep_249(self) -> None:
+        compiler = MySQL84Compiler(pep_249_placeholders=False)
+        compiled = compiler.compile(Query("SELECT * FROM a WHERE x = ?", [1]))
+
+        self.assertTrue(compiled.statement.endswith("x = ?"))
+        self.assertEqual([1], compiled.parameters)
+
+    def test_named_placeholders_are_rewritten(self) -> None:
+        statement = self.template.format(":crit", ":cond")
+        compiler = MySQL84Compiler(
+            placeholder_style="named", output_placeholder_style="numeric"
+        )
+        compiled = compiler.compile(Query(statement, ["CRIT", 42]))
+
+        self.assertIn("criteria = $1", compiled.statement)
+        self.assertIn("b.cond = $2", compiled.statement)
+        self.assertIn("b.cond = $3", compiled.statement)
+        self.assertIn("':crit' AS label", compiled.statement)
+        self.assertEqual(["CRIT", 42, 42], compiled.parameters)
+
+    def test_named_parameters_are_expanded_by_name(self) -> None:
+        statement = self.template.format(":crit", ":cond")
+        compiler = MySQL84Compiler(
+            placeholder_style="named", output_placeholder_style="qmark"
+        )
+        compiled = compiler.compile(Query(statement, {"cond": 42, "crit": "C"}))
+
+        self.assertEqual(["C", 42, 42], compiled.parameters)
+        descriptor = compiler._resolve_compiled_descriptor(statement)
+        self.assertEqual(
+            {"crit": [0], "cond": [1, 2]}, descriptor.parameter_positions
+        )
+
+    def test_missing_named_parameter_is_reported(self) -> None:
+        statement = self.template.format(":crit", ":cond")
+        compiler = MySQL84Compiler(
+            placeholder_style="named", output_placeholder_style="qmark"
+        )
+        with self.assertRaises(KeyError):
+            compiler.compile(Query(statement, {"crit": "C"}))
+
+    def test_named_parameters_are_passed_for_named_output(self) -> None:
+        statement = self.template.format(":crit", ":cond")
+        compiler = MySQL84Compiler(placeholder_style="named")
+        parameters = {"cond": 42, "crit": "C"}
+        compiled = compiler.compile(Query(statement, parameters))
+
+        self.assertEqual(parameters, compiled.parameters)
+
+    def test_pyformat_placeholders_keep_their_names(self) -> None:
+        statement = self.template.format("%(crit)s", "%(cond)s")
+        compiler = MySQL84Compiler(placeholder_style="pyformat")
+        compiled = compiler.compile(Query(statement, ["CRIT", 42]))
+
+        self.assertIn("criteria = %(crit)s", compiled.statement)
+        self.assertEqual(2, compiled.statement.count("b.cond = %(cond)s"))
+
+    def test_invalid_numeric_placeholder_is_reported(self) -> None:
+        compiler = MySQL84Compiler(placeholder_style="numeric")
+        with self.assertRaises(QueryCompilerError) as context:
+            compiler.compile(Query("SELECT * FROM a WHERE x = $0", []))
+
+        self.assertEqual(1, context.exception.line)
+        self.assertEqual(27, context.exception.column)
+
+    def test_unknown_placeholder_style_is_rejected(self) -> None:
+        compiler = MySQL84Compiler(placeholder_style="dollar")  # type: ignore
+        with self.assertRaises(ValueError):
+            compiler.compile(Query("SELECT 1", []))
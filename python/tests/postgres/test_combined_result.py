@@ -0,0 +1,137 @@
+from sqlquerypp import Compiler, PostgreSQLCompiler, Query
+
+from ..common import CompilerTestCase
+
+
+class CombinedResultTests(CompilerTestCase):
+    def _get_compiler(self) -> Compiler:
+        return PostgreSQLCompiler(pretty_print=True)
+
+    def test_with_multiple_parameters_and_union_fragments(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT col_a1 FROM table_a
+                             WHERE criteria = %s) AS $id {
+                SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2
+                FROM table_a a
+                INNER JOIN table_b b
+                ON b.col_a1 = a.col_a1
+                AND b.cond1 = %s
+                WHERE a.col_a1 = $id
+            }
+            UNION ALL
+            combined_result (SELECT col_a1 FROM table_a
+                             WHERE criteria = %s) AS $id {
+                SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2
+                FROM table_a a
+                INNER JOIN table_b b
+                ON b.col_a1 = a.col_a1
+                AND b.cond2 = %s
+                WHERE a.col_a1 = $id
+            }
+            """,
+            ["CRIT1", 1337, "CRIT2", 31415],
+        )
+        expected = Query(
+            self.loadQueryFromFile(
+                __name__,
+                "test_with_multiple_parameters_and_union_fragments",
+            ),
+            ["CRIT1", 1337, "CRIT2", 31415],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_with_parameters_in_nested_combined_result(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM customer WHERE region = %s) AS $cid {
+                SELECT c.id, x.order_id, x.sku
+                FROM customer c
+                INNER JOIN (
+                    combined_result (SELECT id FROM orders
+                                     WHERE state = %s) AS $oid {
+                        SELECT o.id, o.customer_id, i.order_id, i.sku
+                        FROM orders o
+                        INNER JOIN order_item i
+                        ON i.order_id = o.id AND i.sku <> %s
+                        WHERE o.id = $oid
+                    }
+                ) x ON x.customer_id = c.id
+                WHERE c.id = $cid AND c.label <> '$cid'
+            }
+            """,
+            ["REGION", "STATE", "SKU"],
+        )
+        expected = Query(
+            self.loadQueryFromFile(
+                __name__,
+                "test_with_parameters_in_nested_combined_result",
+            ),
+            ["REGION", "STATE", "SKU"],
+        )
+
+        self.assertGeneratedQueryEqual(expected, template)
+
+    def test_with_format_placeholders_in_output(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM a WHERE criteria = %s) AS $id {
+                SELECT a.id, b.x
+                FROM a
+                INNER JOIN b ON b.a_id = a.id AND b.cond = %s
+                WHERE a.id = $id
+            }
+            """,
+            ["CRIT", 42],
+        )
+        compiled = PostgreSQLCompiler(
+            output_placeholder_style="format"
+        ).compile(template)
+
+        self.assertIn("criteria = %s", compiled.statement)
+        self.assertIn("b.cond = %s", compiled.statement)
+        self.assertEqual(["CRIT", 42], compiled.parameters)
+
+    def test_with_backslash_within_string_literal(self) -> None:
+        # backslashes only escape quotes within `E'...'` string literals
+        template = Query(
+            r"""
+            combined_result (SELECT id FROM a
+                             WHERE path <> 'C:\' AND criteria = %s) AS $id {
+                SELECT a.id, b.x
+                FROM a
+                INNER JOIN b ON b.a_id = a.id AND b.note <> E'it\'s'
+                WHERE a.id = $id AND b.cond = %s
+            }
+            """,
+            ["CRIT", 42],
+        )
+        compiled = PostgreSQLCompiler().compile(template)
+
+        self.assertIn("CROSS JOIN LATERAL", compiled.statement)
+        self.assertIn("criteria = $1", compiled.statement)
+        self.assertIn("b.cond = $2", compiled.statement)
+        self.assertEqual(["CRIT", 42], compiled.parameters)
+
+    def test_with_duplicate_iteration_values(self) -> None:
+        # like recursive CTEs, LATERAL joins iterate each value once
+        template = Query(
+            """
+            combined_result (
+                SELECT customer_id FROM orders UNION ALL SELECT 1
+            ) AS $cid {
+                SELECT o.id FROM orders o WHERE o.customer_id = $cid
+            }
+            """,
+            [],
+        )
+        compiled = PostgreSQLCompiler().compile(template)
+
+        self.assertIn(
+            "FROM (SELECT DISTINCT * "
+            "FROM (SELECT customer_id FROM orders UNION ALL SELECT 1) "
+            "AS loop_values (loop_value) "
+            "WHERE loop_value IS NOT NULL) AS loop_values",
+            compiled.statement,
+        )
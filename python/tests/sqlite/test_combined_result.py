@@ -0,0 +1,286 @@
+import sqlite3
+from typing import Any, Optional
+from unittest import TestCase
+
+from sqlquerypp import Query, QueryCompilerError, SQLiteCompiler
+
+SCHEMA = """
+    CREATE TABLE customer (id INTEGER, region TEXT);
+    CREATE TABLE orders (id INTEGER, customer_id INTEGER, state TEXT);
+    CREATE TABLE order_item (order_id INTEGER, sku TEXT);
+
+    INSERT INTO customer VALUES (1, 'EU'), (2, 'EU'), (3, 'US');
+    INSERT INTO orders VALUES
+        (10, 1, 'open'), (11, 1, 'done'), (12, 2, 'open'), (13, 3, 'open');
+    INSERT INTO order_item VALUES
+        (10, 'A'), (10, 'B'), (11, 'C'), (12, 'A'), (13, 'D');
+"""
+
+
+class CombinedResultTests(TestCase):
+    def setUp(self) -> None:
+        self.db = sqlite3.connect(":memory:")
+        self.db.executescript(SCHEMA)
+
+    def tearDown(self) -> None:
+        self.db.close()
+
+    def _execute(
+        self, template: Query, compiler: Optional[SQLiteCompiler] = None
+    ) -> list[Any]:
+        compiled = (compiler or SQLiteCompiler()).compile(template)
+        cursor = self.db.execute(compiled.statement, compiled.parameters)
+        return cursor.fetchall()
+
+    def test_with_union_fragments(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM orders WHERE state = ?) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND i.sku <> ?
+                WHERE o.id = $oid
+            }
+            UNION ALL
+            combined_result (SELECT id FROM orders WHERE state = ?) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND i.sku <> ?
+                WHERE o.id = $oid
+            }
+            """,
+            ["open", "B", "done", "B"],
+        )
+
+        self.assertEqual(
+            [(10, "A"), (12, "A"), (13, "D"), (11, "C")],
+            self._execute(template),
+        )
+
+    def test_with_nested_combined_result(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM customer WHERE region = ?) AS $cid {
+                SELECT c.id, x.order_id, x.sku
+                FROM customer c
+                INNER JOIN (
+                    combined_result (SELECT id FROM orders
+                                     WHERE state = ?) AS $oid {
+                        SELECT o.id, o.customer_id, i.order_id, i.sku
+                        FROM orders o
+                        INNER JOIN order_item i
+                        ON i.order_id = o.id AND i.sku <> ?
+                        WHERE o.id = $oid
+                    }
+                ) x ON x.customer_id = c.id
+                WHERE c.id = $cid
+            }
+            """,
+            ["EU", "open", "B"],
+        )
+
+        self.assertEqual(
+            [(1, 10, "A"), (2, 12, "A")],
+            self._execute(template),
+        )
+
+    def test_with_named_parameters(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM orders WHERE state = :state) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND i.sku <> :sku
+                WHERE o.id = $oid
+            }
+            """,
+            {"state": "open", "sku": "A"},
+        )
+        compiler = SQLiteCompiler(placeholder_style="named")
+
+        self.assertEqual(
+            [(10, "B"), (13, "D")],
+            self._execute(template, compiler),
+        )
+
+    def test_within_insert_and_create_view(self) -> None:
+        # views can't have parameters
+        inner_query = """
+            combined_result (SELECT id FROM orders WHERE state = %s) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND i.sku <> 'B'
+                WHERE o.id = $oid
+            }
+        """
+        self.db.execute("CREATE TABLE report (order_id INTEGER, sku TEXT)")
+        self._execute(
+            Query(
+                f"INSERT INTO report (order_id, sku) {inner_query % '?'}",
+                ["done"],
+            )
+        )
+        self._execute(
+            Query(
+                f"CREATE VIEW open_items AS {inner_query % repr('open')}", []
+            )
+        )
+
+        self.assertEqual(
+            [(11, "C")],
+            self.db.execute("SELECT * FROM report").fetchall(),
+        )
+        self.assertEqual(
+            [(10, "A"), (12, "A"), (13, "D")],
+            self.db.execute("SELECT * FROM open_items").fetchall(),
+        )
+
+    def test_with_iteration_variable_within_and_chain(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM orders WHERE id > 10) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id
+                WHERE o.state = ? AND ($oid = o.id AND i.sku <> 'D')
+            }
+            """,
+            ["open"],
+        )
+
+        self.assertEqual(
+            [(12, "A")],
+            self._execute(template),
+        )
+
+    def test_with_iteration_variable_within_join_condition(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM orders WHERE id > 10) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND o.id = $oid
+                WHERE o.state = ?
+            }
+            """,
+            ["open"],
+        )
+
+        self.assertEqual(
+            [(12, "A"), (13, "D")],
+            self._execute(template),
+        )
+
+    def test_with_key_column_neither_projected_nor_named_alike(self) -> None:
+        template = Query(
+            """
+            combined_result (SELECT id FROM customer WHERE id > 1) AS $cid {
+                SELECT o.id, o.state FROM orders o WHERE o.customer_id = $cid
+            }
+            """,
+            [],
+        )
+
+        self.assertEqual(
+            [(12, "open"), (13, "open")],
+            self._execute(template),
+        )
+
+    def test_with_unordered_and_duplicate_iteration_values(self) -> None:
+        template = Query(
+            """
+            combined_result (
+                SELECT customer_id FROM orders
+                UNION ALL SELECT 99
+                UNION ALL SELECT 1
+            ) AS $cid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id
+                WHERE o.customer_id = $cid
+            }
+            """,
+            [],
+        )
+
+        self.assertEqual(
+            [(10, "A"), (10, "B"), (11, "C"), (12, "A"), (13, "D")],
+            sorted(self._execute(template)),
+        )
+
+    def test_with_composite_iteration_values(self) -> None:
+        template = Query(
+            """
+            combined_result (
+                SELECT customer_id, id FROM orders
+                UNION ALL SELECT 1, 10
+            ) AS ($cid, $oid) {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND i.sku <> 'B'
+                WHERE o.customer_id = $cid AND o.id = $oid
+            }
+            """,
+            [],
+        )
+
+        self.assertEqual(
+            [(10, "A"), (11, "C"), (12, "A"), (13, "D")],
+            sorted(self._execute(template)),
+        )
+
+    def test_with_null_within_composite_iteration_values(self) -> None:
+        # tuples containing NULL are skipped, like NULL keys are
+        template = Query(
+            """
+            combined_result (
+                SELECT customer_id, id FROM orders
+                UNION ALL SELECT NULL, 10
+                UNION ALL SELECT 2, NULL
+            ) AS ($cid, $oid) {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND i.sku <> 'B'
+                WHERE o.customer_id = $cid AND o.id = $oid
+            }
+            """,
+            [],
+        )
+
+        self.assertEqual(
+            [(10, "A"), (11, "C"), (12, "A"), (13, "D")],
+            sorted(self._execute(template)),
+        )
+
+    def test_with_backslash_within_string_literal(self) -> None:
+        # backslashes don't escape quotes within SQLite string literals
+        template = Query(
+            r"""
+            combined_result (
+                SELECT id FROM orders WHERE state <> 'C:\' AND state = ?
+            ) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id
+                WHERE o.id = $oid
+            }
+            """,
+            ["done"],
+        )
+
+        self.assertEqual([(11, "C")], self._execute(template))
+
+    def test_ambiguous_iteration_variable_is_reported(self) -> None:
+        for condition in ["o.id = $oid OR o.state = 'open'", "o.id > $oid"]:
+            template = Query(
+                f"""
+                combined_result (SELECT id FROM orders) AS $oid {{
+                    SELECT o.id, o.state FROM orders o WHERE {condition}
+                }}
+                """,
+                [],
+            )
+
+            with self.assertRaises(QueryCompilerError) as context:
+                self._execute(template)
+            self.assertIn("$oid", str(context.exception))
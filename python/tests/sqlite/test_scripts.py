@@ -0,0 +1,59 @@
+import sqlite3
+from unittest import TestCase
+
+from sqlquerypp import Query, QueryCompilerError, SQLiteCompiler
+
+from .test_combined_result import SCHEMA
+
+
+class ScriptTests(TestCase):
+    def setUp(self) -> None:
+        self.db = sqlite3.connect(":memory:")
+        self.db.executescript(SCHEMA)
+
+    def tearDown(self) -> None:
+        self.db.close()
+
+    def test_with_combined_result_statement(self) -> None:
+        script = Query(
+            """
+            -- archive all open orders of a region; keep the others
+            CREATE TABLE archive (order_id INTEGER, sku TEXT);
+
+            INSERT INTO archive
+            combined_result (
+                SELECT o.id FROM orders o
+                INNER JOIN customer c ON c.id = o.customer_id
+                WHERE c.region = ? AND o.state = 'open'
+            ) AS $oid {
+                SELECT o.id, i.sku
+                FROM orders o
+                INNER JOIN order_item i ON i.order_id = o.id AND i.sku <> ?
+                WHERE o.id = $oid
+            };
+
+            DELETE FROM orders WHERE state = 'open' AND customer_id IN (
+                SELECT id FROM customer WHERE region = ?
+            );
+            """,
+            ["EU", "B", "EU"],
+        )
+
+        compiled = SQLiteCompiler().compile_script(script)
+        self.assertEqual(3, len(compiled))
+        self.assertEqual([], compiled[0].parameters)
+        self.assertEqual(["EU"], compiled[2].parameters)
+        for query in compiled:
+            self.db.execute(query.statement, query.parameters)
+
+        archived = self.db.execute("SELECT * FROM archive ORDER BY 1")
+        self.assertEqual([(10, "A"), (12, "A")], archived.fetchall())
+        remaining = self.db.execute("SELECT id FROM orders ORDER BY 1")
+        self.assertEqual([(11,), (13,)], remaining.fetchall())
+
+    def test_errors_point_into_script(self) -> None:
+        script = Query("SELECT 1;\nSELECT 2 FROM;", [])
+
+        with self.assertRaises(QueryCompilerError) as context:
+            SQLiteCompiler().compile_script(script)
+        self.assertEqual(2, context.exception.line)
@@ -1,26 +1,571 @@
 use {
-    crate::error::QueryCompilerError,
-    sqlparser::{dialect::GenericDialect, parser::Parser},
+    crate::{
+        codegen::{Dialect, Feature},
+        error::{QueryCompilerError, SourceLocation},
+        lex::{
+            self,
+            QuotingStyle,
+            Span,
+            TokenKind,
+            KEYWORD_FROM,
+            KEYWORD_JOIN,
+            KEYWORD_LATERAL,
+        },
+        parser::{FinalParserState, Node},
+        scanner::Scanner,
+        types::{CombinedResultNodeSlice, CompiledQueryDescriptor},
+    },
+    sqlparser::{
+        dialect::{Dialect as ParserDialect, MySqlDialect},
+        keywords::Keyword,
+        parser::{Parser, ParserError},
+        tokenizer::Token,
+    },
+    std::{cmp::Reverse, collections::BTreeMap},
 };
 
+/// Compiles all `nodes` of `state` (see `process_nodes_in_order`) and
+/// validates the resulting statement as SQL of the given `dialect`.
+///
+/// Everything outside the directives is kept byte-for-byte (including
+/// comments like optimizer hints), unless `pretty_print` is set. The latter
+/// reformats the resulting statement as a whole, see
+/// `format_query_prettily`.
+///
+/// Numbered placeholders (see `crate::parameters`) are kept as is, so that
+/// the `parameter_order` of the result is still empty. See `crate::compile`
+/// for resolving it.
+pub fn compile_statement<TNode: Node>(
+    state: &mut FinalParserState,
+    nodes: Vec<TNode>,
+    generate_code: impl Fn(&TNode) -> Result<String, QueryCompilerError>,
+    dialect: &dyn Dialect,
+    pretty_print: bool)
+    -> Result<CompiledQueryDescriptor, QueryCompilerError> {
+    let template = state.statement.clone();
+    let replacements = process_nodes_in_order(state, nodes, generate_code)?;
+
+    let combined_result_node_slices = get_combined_result_node_slices(state);
+    let parser_dialect = dialect.parser_dialect();
+    let formatted =
+        format_query_prettily(&state.statement, parser_dialect.as_ref())
+            .map_err(|err| {
+                let locate = |offset| {
+                    locate_in_template(offset,
+                                       &template,
+                                       &replacements,
+                                       dialect.quoting_style())
+                };
+                locate_resulting_query_error(err, &state.statement, locate)
+            })?;
+    let statement = match pretty_print
+    {
+        true => formatted,
+        false => std::mem::take(&mut state.statement),
+    };
+    Ok(CompiledQueryDescriptor { statement,
+                                 combined_result_node_slices,
+                                 parameter_order: vec![],
+                                 parameter_positions: BTreeMap::new() })
+}
+
+/// Fails if the template `statement` uses `LATERAL` derived tables, but the
+/// `dialect` doesn't support them. Apart from that, only the code generated
+/// for directives is gated by the features of the dialect (see
+/// `NodeCompiler`), while the syntax of the template is left to the
+/// database.
+///
+/// `LATERAL` is only taken as such if it follows `FROM`, `JOIN` or `,` and
+/// precedes `(`, as it may be an identifier otherwise (e.g. on SQLite).
+/// Occurrences within string literals, quoted identifiers and comments are
+/// skipped.
+pub fn check_template_syntax(statement: &str,
+                             dialect: &dyn Dialect)
+                             -> Result<(), QueryCompilerError> {
+    let tokens =
+        Scanner::new(statement).with_quoting_style(dialect.quoting_style())
+                               .filter(|token| {
+                                   !matches!(token.kind,
+                                             TokenKind::Whitespace
+                                             | TokenKind::Comment)
+                               })
+                               .collect::<Vec<_>>();
+    let mut errors = vec![];
+    for (index, token) in tokens.iter().enumerate()
+    {
+        let preceding =
+            index.checked_sub(1).and_then(|index| tokens.get(index));
+        if !is_lateral_derived_table(token,
+                                     preceding,
+                                     tokens.get(index + 1),
+                                     statement)
+        {
+            continue;
+        }
+        if let Err(requirement) = dialect.require(Feature::LateralJoin)
+        {
+            errors.push(QueryCompilerError::UnsupportedSyntax(
+                Feature::LateralJoin.to_string(),
+                dialect.to_string(),
+                requirement,
+                token.span,
+            ));
+        }
+    }
+    match errors.is_empty()
+    {
+        true => Ok(()),
+        false => Err(QueryCompilerError::from_errors(errors)),
+    }
+}
+
+/// Whether `token` is the `LATERAL` keyword of a derived table, judging by
+/// the `preceding` and `succeeding` tokens (apart from whitespace and
+/// comments).
+fn is_lateral_derived_table(token: &lex::Token,
+                            preceding: Option<&lex::Token>,
+                            succeeding: Option<&lex::Token>,
+                            statement: &str)
+                            -> bool {
+    let is_word = |token: &lex::Token, word: &str| {
+        token.kind == TokenKind::Word
+        && token.text(statement).eq_ignore_ascii_case(word)
+    };
+    let follows_table_reference =
+        preceding.is_some_and(|preceding| {
+                     is_word(preceding, KEYWORD_FROM)
+                     || is_word(preceding, KEYWORD_JOIN)
+                     || preceding.text(statement) == ","
+                 });
+    is_word(token, KEYWORD_LATERAL)
+    && follows_table_reference
+    && succeeding.is_some_and(|succeeding| {
+                     succeeding.kind == TokenKind::OpeningParenthese
+                 })
+}
+
+/// Wraps generated code, so that it can replace a directive within a
+/// compound query (like `... UNION ALL combined_result ...`).
+///
+/// Dialects which can't parse parenthesized operands of compound queries
+/// get a plain `SELECT` instead.
+pub fn embed_into_compound_query(code: String,
+                                 dialect: &dyn Dialect)
+                                 -> String {
+    match dialect.supports(Feature::ParenthesizedCompoundOperands)
+    {
+        true => format!("({code})"),
+        false => format!("SELECT * FROM ({code})"),
+    }
+}
+
+/// Compiles all nodes and splices the generated code into the spans of the
+/// original directives. The code generated by `generate_code` replaces each
+/// directive as is, i.e. it must be parenthesized if required by the
+/// dialect.
+///
+/// Returns the spans of the replaced directives, along with the length of
+/// their generated code (see `locate_in_template`).
+pub fn process_nodes_in_order<TNode: Node>(
+    state: &mut FinalParserState,
+    nodes: Vec<TNode>,
+    generate_code: impl Fn(&TNode) -> Result<String, QueryCompilerError>)
+    -> Result<Vec<(Span, usize)>, QueryCompilerError> {
+    let top_level_code = compile_nodes(&state.statement, nodes, generate_code)?;
+    let mut replacements =
+        top_level_code.iter()
+                      .map(|(span, code)| (*span, code.len()))
+                      .collect::<Vec<_>>();
+    replacements.sort_by_key(|(span, _)| span.begin);
+
+    let whole_statement = Span::new(0, state.statement.len());
+    state.statement = splice_generated_code(&state.statement,
+                                            whole_statement,
+                                            top_level_code);
+
+    Ok(replacements)
+}
+
+/// Maps the `offset` within the spliced statement back to the `template`,
+/// given the sorted `replacements` of `process_nodes_in_order`.
+///
+/// Offsets within generated code map to the span of the replaced directive,
+/// others to the span of the token of the template at that offset.
+fn locate_in_template(offset: usize,
+                      template: &str,
+                      replacements: &[(Span, usize)],
+                      quoting_style: QuotingStyle)
+                      -> Span {
+    let mut template_cursor = 0;
+    let mut spliced_cursor = 0;
+    for (span, generated_length) in replacements.iter()
+    {
+        let copied_length = span.begin - template_cursor;
+        if offset < spliced_cursor + copied_length
+        {
+            break;
+        }
+        spliced_cursor += copied_length;
+        if offset < spliced_cursor + generated_length
+        {
+            return *span;
+        }
+        spliced_cursor += generated_length;
+        template_cursor = span.end;
+    }
+
+    let offset = template_cursor + offset - spliced_cursor;
+    Scanner::starting_at(template, offset).with_quoting_style(quoting_style)
+                                          .next()
+                                          .map_or(Span::new(offset, offset),
+                                                  |token| token.span)
+}
+
+/// The location `sqlparser` appends to the messages of its errors, e.g.
+/// `Expected: ), found: EOF at Line: 1, Column: 8`.
+const PARSER_ERROR_LINE: &str = " at Line: ";
+const PARSER_ERROR_COLUMN: &str = ", Column: ";
+
+/// Points an error of the resulting `statement` (see
+/// `format_query_prettily`) at the template, using `locate` to map offsets
+/// within `statement` back to it. The location is removed from the message
+/// of the parser error, as it refers to the resulting statement.
+///
+/// Errors without a location (e.g. at the end of the statement) point at
+/// the end of the template.
+fn locate_resulting_query_error(err: QueryCompilerError,
+                                statement: &str,
+                                locate: impl Fn(usize) -> Span)
+                                -> QueryCompilerError {
+    let QueryCompilerError::ResultingQueryInvalid(error, _) = err
+    else
+    {
+        return err;
+    };
+    let (error, location) = split_parser_error_location(error);
+    let span = match location.and_then(|location| location.to_offset(statement))
+    {
+        Some(offset) => locate(offset),
+        None => locate(statement.len()),
+    };
+    QueryCompilerError::ResultingQueryInvalid(error, span)
+}
+
+/// Splits the location off the message of a parser error, if any.
+fn split_parser_error_location(error: ParserError)
+                               -> (ParserError, Option<SourceLocation>) {
+    let split = |message: String| {
+        let location =
+            message.rfind(PARSER_ERROR_LINE).and_then(|index| {
+                let (line, column) = message[index + PARSER_ERROR_LINE.len() ..]
+                    .split_once(PARSER_ERROR_COLUMN)?;
+                let location = SourceLocation { line: line.parse().ok()?,
+                                                column: column.parse().ok()? };
+                Some((index, location))
+            });
+        match location
+        {
+            Some((index, location)) =>
+            {
+                (message[.. index].into(), Some(location))
+            },
+            None => (message, None),
+        }
+    };
+    match error
+    {
+        ParserError::ParserError(message) =>
+        {
+            let (message, location) = split(message);
+            (ParserError::ParserError(message), location)
+        },
+        ParserError::TokenizerError(message) =>
+        {
+            let (message, location) = split(message);
+            (ParserError::TokenizerError(message), location)
+        },
+        error => (error, None),
+    }
+}
+
+/// Compiles the given nodes and returns the generated code of all top level
+/// nodes, together with the span it replaces.
+///
+/// Nested nodes are compiled first, so that their generated code can be
+/// embedded into the enclosing node before the latter is compiled. The errors
+/// of all nodes are reported at once. Nodes enclosing a node which failed are
+/// skipped, as their errors would merely be a consequence.
+fn compile_nodes<TNode: Node>(
+    statement: &str,
+    mut nodes: Vec<TNode>,
+    generate_code: impl Fn(&TNode) -> Result<String, QueryCompilerError>)
+    -> Result<Vec<(Span, String)>, QueryCompilerError> {
+    nodes.sort_by_key(|node| Reverse(node.get_depth()));
+
+    let mut errors = vec![];
+    let mut compiled_nodes: Vec<(TNode, Option<String>)> = vec![];
+    for mut node in nodes.into_iter()
+    {
+        let nested_code: Option<Vec<(Span, String)>> =
+            compiled_nodes.iter()
+                          .filter(|(nested, _)| {
+                              is_directly_nested(&node, nested)
+                          })
+                          .map(|(nested, generated_code)| {
+                              generated_code.clone()
+                                            .map(|code| {
+                                                (nested.get_span(), code)
+                                            })
+                          })
+                          .collect();
+        let Some(nested_code) = nested_code
+        else
+        {
+            compiled_nodes.push((node, None));
+            continue;
+        };
+
+        node.embed_nested_code(statement, nested_code);
+        // Errors of the inner query don't know their location within the
+        // template, so they point at the body of the directive instead.
+        let scope = Span::new(node.get_scope_begin_position(),
+                              node.get_end_position() + 1);
+        match generate_code(&node)
+        {
+            Ok(generated_code) =>
+            {
+                compiled_nodes.push((node, Some(generated_code)))
+            },
+            Err(err) =>
+            {
+                errors.push(err.locate_within(scope));
+                compiled_nodes.push((node, None));
+            },
+        }
+    }
+
+    if !errors.is_empty()
+    {
+        return Err(QueryCompilerError::from_errors(errors));
+    }
+
+    Ok(compiled_nodes.into_iter()
+                     .filter(|(node, _)| node.get_depth() == 0)
+                     .filter_map(|(node, generated_code)| {
+                         generated_code.map(|code| (node.get_span(), code))
+                     })
+                     .collect())
+}
+
+fn is_directly_nested(node: &impl Node, candidate: &impl Node) -> bool {
+    candidate.get_depth() == node.get_depth() + 1 && node.encloses(candidate)
+}
+
+/// Returns the slices of all `combined_result` directives of the template.
+pub fn get_combined_result_node_slices(state: &FinalParserState)
+                                       -> Vec<CombinedResultNodeSlice> {
+    state.combined_result_nodes
+         .iter()
+         .map(|node| {
+             CombinedResultNodeSlice { scope_begin:
+                                           node.get_scope_begin_position(),
+                                       scope_end: node.get_end_position() }
+         })
+         .collect()
+}
+
+/// Copies the `range` of `statement`, while replacing each of the given
+/// spans by its generated code in a single pass.
+///
+/// The replaced spans must be located within `range` and must not overlap
+/// each other. They don't need to be sorted.
+pub fn splice_generated_code(statement: &str,
+                             range: Span,
+                             mut replacements: Vec<(Span, String)>)
+                             -> String {
+    replacements.sort_by_key(|(span, _)| span.begin);
+
+    let mut spliced = String::with_capacity(range.end - range.begin);
+    let mut cursor = range.begin;
+    for (span, generated_code) in replacements.iter()
+    {
+        spliced.push_str(&statement[cursor .. span.begin]);
+        spliced.push_str(generated_code);
+        cursor = span.end;
+    }
+    spliced.push_str(&statement[cursor .. range.end]);
+
+    spliced
+}
+
+/// The legacy locking clause of MySQL, which `sqlparser` doesn't know. It
+/// is kept as is by `format_query_prettily`.
+const LOCK_IN_SHARE_MODE: [Keyword; 4] =
+    [Keyword::LOCK, Keyword::IN, Keyword::SHARE, Keyword::MODE];
+
 /// Reformats (i.e. indents and normalizes) a given SQL string to make
 /// it more human-readable.
 ///
-/// This also ensures the query is valid SQL as far the `sqlparser`
-/// crate can tell. In case the passed SQL string is invalid, an
-/// according error is returned.
-pub fn format_query_prettily(query: &str)
+/// This also ensures the query is valid SQL of the given `dialect` as far
+/// the `sqlparser` crate can tell. In case the passed SQL string is invalid,
+/// an according error is returned. Besides queries, any statement containing
+/// a query is accepted, e.g. `INSERT ... SELECT` or `CREATE VIEW ... AS`.
+pub fn format_query_prettily(query: &str,
+                             dialect: &dyn ParserDialect)
                              -> Result<String, QueryCompilerError> {
-    let parser = Parser::new(&GenericDialect {});
-    let parsed =
-        parser.try_with_sql(query)
-              .map_err(|e| {
-                  QueryCompilerError::ResultingQueryInvalid(query.into(), e)
-              })?
-              .parse_query()
-              .map_err(|e| {
-                  QueryCompilerError::ResultingQueryInvalid(query.into(), e)
-              })?;
-
-    Ok(format!("{:#}", parsed))
+    let invalid = |err: ParserError| {
+        QueryCompilerError::ResultingQueryInvalid(err, Span::default())
+    };
+    let mut parser =
+        Parser::new(dialect).try_with_sql(query).map_err(invalid)?;
+    let parsed = parser.parse_statement().map_err(invalid)?;
+    let lock_clause = dialect.is::<MySqlDialect>()
+                      && parser.parse_keywords(&LOCK_IN_SHARE_MODE);
+
+    // The query ends wherever the parser doesn't know how to continue, so
+    // that unsupported trailing syntax would be dropped silently otherwise.
+    let _ = parser.consume_token(&Token::SemiColon);
+    parser.expect_token(&Token::EOF).map_err(invalid)?;
+
+    match lock_clause
+    {
+        true => Ok(format!("{parsed:#} LOCK IN SHARE MODE")),
+        false => Ok(format!("{:#}", parsed)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            codegen::{
+                common::{
+                    format_query_prettily,
+                    locate_in_template,
+                    splice_generated_code,
+                },
+                Dialect,
+                MariaDb,
+                MySql80,
+                MySql84,
+            },
+            compile,
+            error::QueryCompilerError,
+            lex::{QuotingStyle, Span},
+        },
+        sqlparser::dialect::{MySqlDialect, PostgreSqlDialect},
+    };
+
+    #[test]
+    fn identical_spans_are_replaced_individually() {
+        let statement = "x AND x AND x";
+        let replacements = vec![(Span::new(12, 13), "c".to_string()),
+                                (Span::new(0, 1), "a".to_string()),];
+
+        assert_eq!("a AND x AND c",
+                   splice_generated_code(statement,
+                                         Span::new(0, statement.len()),
+                                         replacements));
+    }
+
+    #[test]
+    fn only_given_range_is_copied() {
+        let replacements = vec![(Span::new(4, 5), "b".to_string())];
+
+        assert_eq!(" b ",
+                   splice_generated_code("x ( y ) z",
+                                         Span::new(3, 6),
+                                         replacements));
+    }
+
+    #[test]
+    fn trailing_syntax_is_validated() {
+        let query = "SELECT a FROM t WHERE a = 1 LOCK IN SHARE MODE";
+
+        assert_eq!("SELECT\n  a\nFROM\n  t\nWHERE\n  a = 1 LOCK IN SHARE MODE",
+                   format_query_prettily(query, &MySqlDialect {}).unwrap());
+        assert!(format_query_prettily(query, &PostgreSqlDialect {}).is_err());
+        assert!(format_query_prettily("SELECT a FROM t WHERE a = 1 b",
+                                      &MySqlDialect {}).is_err());
+    }
+
+    #[test]
+    fn statements_containing_queries_are_accepted() {
+        for statement in ["INSERT INTO t (a) SELECT a FROM s",
+                          "CREATE VIEW v AS SELECT a FROM s",
+                          "CREATE TABLE t AS SELECT a FROM s"]
+        {
+            let formatted =
+                format_query_prettily(statement, &MySqlDialect {}).unwrap();
+            assert!(formatted.starts_with(&statement[.. 11]));
+        }
+    }
+
+    #[test]
+    fn directives_within_statements_containing_queries_are_compiled() {
+        let directive = "combined_result (SELECT id FROM a WHERE x = ?) \
+                         AS $id { SELECT b.a_id FROM b WHERE b.a_id = $id }";
+        for prefix in ["INSERT INTO t (a) ",
+                       "CREATE VIEW v AS ",
+                       "CREATE TABLE t AS "]
+        {
+            let template = format!("{prefix}{directive}");
+            for dialect in [&MySql84 as &dyn Dialect,
+                            &MySql80::default(),
+                            &MariaDb::default()]
+            {
+                let compiled = compile(&template, dialect).unwrap();
+                let statement = &compiled.statement;
+                assert!(statement.starts_with(&format!("{prefix}(WITH \
+                                                        RECURSIVE ")),
+                        "{dialect}: {statement}");
+                assert!(!statement.contains("$id"), "{dialect}: {statement}");
+                assert_eq!(vec![0], compiled.parameter_order, "{dialect}");
+
+                // The slice is the body of the directive within the template.
+                let slice = &compiled.combined_result_node_slices[0];
+                assert_eq!(template.find('{'), Some(slice.scope_begin));
+                assert_eq!(template.len() - 1, slice.scope_end);
+            }
+        }
+    }
+
+    #[test]
+    fn offsets_of_spliced_statement_are_located_in_template() {
+        let template = "SELECT a FROM (DIRECTIVE) x WHERE b";
+        // `DIRECTIVE` has been replaced by 20 bytes of generated code.
+        let replacements = [(Span::new(15, 24), 20)];
+        let locate = |offset| {
+            locate_in_template(offset,
+                               template,
+                               &replacements,
+                               QuotingStyle::MySql)
+        };
+
+        assert_eq!(Span::new(7, 8), locate(7));
+        assert_eq!(Span::new(15, 24), locate(15));
+        assert_eq!(Span::new(15, 24), locate(34));
+        assert_eq!(Span::new(24, 25), locate(35));
+        assert_eq!(Span::new(34, 35), locate(45));
+        assert_eq!(Span::new(35, 35), locate(46));
+    }
+
+    #[test]
+    fn errors_of_resulting_query_point_at_offending_token() {
+        let directive = "combined_result (SELECT id FROM a) AS $id { \
+                         SELECT b.id FROM b WHERE b.a_id = $id }";
+        for template in ["SELECT a FROM a WHERE a.x = 1 foo".to_string(),
+                         format!("SELECT x.id FROM ({directive}) x \
+                                  WHERE x.id = 1 foo")]
+        {
+            let err = compile(&template, &MySql84).unwrap_err();
+            assert!(matches!(err,
+                             QueryCompilerError::ResultingQueryInvalid(..)));
+            let span = err.span();
+            assert_eq!("foo", &template[span.begin .. span.end]);
+            assert_eq!("resulting query is invalid: sql parser error: \
+                        Expected: EOF, found: foo",
+                       err.to_string());
+        }
+    }
 }
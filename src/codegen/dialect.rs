@@ -0,0 +1,199 @@
+use {
+    crate::lex::{PlaceholderStyle, QuotingStyle},
+    std::{fmt, str::FromStr},
+};
+
+/// A SQL dialect `sqlquerypp` can compile query templates to.
+///
+/// Code generation doesn't know the individual dialects. Instead, it picks
+/// its constructs by the capabilities of the dialect (see `Feature`), so
+/// that adding a dialect merely means implementing this trait.
+pub trait Dialect: fmt::Debug + fmt::Display {
+    /// Whether the dialect supports `feature`, possibly depending on the
+    /// declared server version.
+    fn support(&self, feature: Feature) -> Support;
+
+    /// The declared version of the target server, if the features of the
+    /// dialect depend on it.
+    fn server_version(&self) -> Option<ServerVersion> {
+        None
+    }
+
+    /// The dialect of `sqlparser`, which parses and validates queries.
+    fn parser_dialect(&self) -> Box<dyn sqlparser::dialect::Dialect>;
+
+    /// The lexical rules of string literals, quoted identifiers and comments
+    /// within templates of the dialect.
+    fn quoting_style(&self) -> QuotingStyle {
+        QuotingStyle::MySql
+    }
+
+    /// The style of numbered placeholders within the compiled statement,
+    /// before they are resolved (see `crate::parameters`).
+    fn numbered_placeholder_style(&self) -> PlaceholderStyle {
+        PlaceholderStyle::Qmark
+    }
+
+    /// The style of the placeholders within the resulting query, unless
+    /// requested otherwise. `None` keeps the style of the template.
+    fn default_output_placeholder_style(&self) -> Option<PlaceholderStyle> {
+        None
+    }
+
+    /// Whether iterations are compiled to `LATERAL` joins instead of
+    /// recursive common table expressions, given that both are supported.
+    fn prefers_lateral_joins(&self) -> bool {
+        true
+    }
+
+    fn supports(&self, feature: Feature) -> bool {
+        match self.support(feature)
+        {
+            Support::Always => true,
+            Support::Never => false,
+            Support::Since(required) =>
+            {
+                self.server_version()
+                    .is_some_and(|version| version >= required)
+            },
+        }
+    }
+
+    /// Fails with the unmet requirement, unless the dialect supports
+    /// `feature`.
+    fn require(&self, feature: Feature) -> Result<(), String> {
+        if self.supports(feature)
+        {
+            return Ok(());
+        }
+        match self.support(feature)
+        {
+            Support::Since(required) =>
+            {
+                Err(format!("{feature} require version {required} or later, \
+                             declare a newer server version"))
+            },
+            _ => Err(format!("{feature} aren't supported by {self}")),
+        }
+    }
+}
+
+/// Features of SQL dialects which templates or code generation depend on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// `WITH RECURSIVE ...`
+    RecursiveCte,
+    /// `WITH` clauses within the definition of a common table expression.
+    NestedCte,
+    /// `LATERAL` derived tables, within templates or generated code.
+    LateralJoin,
+    /// `JSON_TABLE(...)`
+    JsonTable,
+    /// Optimizer hints within `/*+ ... */` comments. These aren't gated, as
+    /// other targets take them as plain comments (or as hints of extensions
+    /// like `pg_hint_plan`).
+    OptimizerHints,
+    /// Parenthesized operands of compound queries, e.g.
+    /// `(SELECT ...) UNION ALL (SELECT ...)`.
+    ParenthesizedCompoundOperands,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self
+        {
+            Self::RecursiveCte => "recursive common table expressions",
+            Self::NestedCte => "nested common table expressions",
+            Self::LateralJoin => "`LATERAL` derived tables",
+            Self::JsonTable => "`JSON_TABLE` functions",
+            Self::OptimizerHints => "optimizer hints",
+            Self::ParenthesizedCompoundOperands =>
+            {
+                "parenthesized compound operands"
+            },
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// Whether a dialect supports a `Feature`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Support {
+    Always,
+    Never,
+    /// Supported since the given server version.
+    Since(ServerVersion),
+}
+
+/// The version of a database server, e.g. `8.0.35`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major,
+               minor,
+               patch }
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = String;
+
+    /// Parses versions like `8.0`, `8.0.35` or `10.11.6-MariaDB` (as reported
+    /// by `SELECT VERSION()`). An omitted patch version defaults to 0.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid server version `{value}`");
+        let release = value.split('-').next().unwrap_or_default();
+        let numbers = release.split('.')
+                             .map(|number| number.parse::<u32>())
+                             .collect::<Result<Vec<_>, _>>()
+                             .map_err(|_| invalid())?;
+        match numbers[..]
+        {
+            [major, minor] => Ok(Self::new(major, minor, 0)),
+            [major, minor, patch] => Ok(Self::new(major, minor, patch)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{Dialect, Feature, MySql80, ServerVersion, Sqlite};
+
+    #[test]
+    fn server_versions_are_parsed() {
+        assert_eq!(Ok(ServerVersion::new(8, 0, 0)), "8.0".parse());
+        assert_eq!(Ok(ServerVersion::new(8, 0, 35)), "8.0.35".parse());
+        assert_eq!(Ok(ServerVersion::new(10, 11, 6)),
+                   "10.11.6-MariaDB-log".parse());
+        assert!("8".parse::<ServerVersion>().is_err());
+        assert!("8.x".parse::<ServerVersion>().is_err());
+    }
+
+    #[test]
+    fn features_are_gated_by_server_version() {
+        let dialect = MySql80(ServerVersion::new(8, 0, 13));
+        assert!(dialect.supports(Feature::RecursiveCte));
+        assert!(!dialect.supports(Feature::LateralJoin));
+        assert_eq!(Err("`LATERAL` derived tables require version 8.0.14 or \
+                        later, declare a newer server version"
+                                                              .into()),
+                   dialect.require(Feature::LateralJoin));
+
+        assert_eq!(Err("`JSON_TABLE` functions aren't supported by SQLite"
+                                                                    .into()),
+                   Sqlite.require(Feature::JsonTable));
+    }
+}
@@ -0,0 +1,55 @@
+use {
+    crate::codegen::{Dialect, Feature, ServerVersion, Support},
+    sqlparser::dialect::MySqlDialect,
+    std::fmt,
+};
+
+/// The version of the target server, unless declared otherwise. This is the
+/// first GA release of MariaDB 10.11.
+pub const DEFAULT_SERVER_VERSION: ServerVersion = ServerVersion::new(10, 11, 2);
+
+/// MariaDB, given the declared version of the target server.
+///
+/// MariaDB doesn't support `LATERAL` derived tables at all, hence
+/// `combined_result` directives are compiled to recursive common table
+/// expressions. CTEs used by other CTEs are declared alongside them, instead
+/// of within their definitions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MariaDb(pub ServerVersion);
+
+impl Default for MariaDb {
+    fn default() -> Self {
+        Self(DEFAULT_SERVER_VERSION)
+    }
+}
+
+impl Dialect for MariaDb {
+    fn support(&self, feature: Feature) -> Support {
+        match feature
+        {
+            Feature::RecursiveCte =>
+            {
+                Support::Since(ServerVersion::new(10, 2, 2))
+            },
+            Feature::JsonTable => Support::Since(ServerVersion::new(10, 6, 0)),
+            Feature::ParenthesizedCompoundOperands => Support::Always,
+            Feature::NestedCte
+            | Feature::LateralJoin
+            | Feature::OptimizerHints => Support::Never,
+        }
+    }
+
+    fn server_version(&self) -> Option<ServerVersion> {
+        Some(self.0)
+    }
+
+    fn parser_dialect(&self) -> Box<dyn sqlparser::dialect::Dialect> {
+        Box::new(MySqlDialect {})
+    }
+}
+
+impl fmt::Display for MariaDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MariaDB {}", self.0)
+    }
+}
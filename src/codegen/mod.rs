@@ -1,4 +1,208 @@
-mod common;
+use crate::{
+    codegen::common::{
+        check_template_syntax,
+        compile_statement,
+        embed_into_compound_query,
+    },
+    error::QueryCompilerError,
+    lex::PlaceholderStyle,
+    parameters::to_numeric_placeholders,
+    parser::{FinalParserState, Node},
+    types::CompiledQueryDescriptor,
+};
+
+pub mod common;
+pub mod dialect;
+pub mod mariadb;
+pub mod mysql80;
 pub mod mysql84;
+pub mod postgres;
+pub mod sqlite;
+
+pub use {
+    dialect::{Dialect, Feature, ServerVersion, Support},
+    mariadb::MariaDb,
+    mysql80::MySql80,
+    mysql84::MySql84,
+    postgres::PostgreSql,
+    sqlite::Sqlite,
+};
+
+/// The names of all dialects, see `get_dialect`.
+pub const DIALECT_NAMES: [&str; 5] =
+    ["mysql84", "mysql80", "mariadb", "postgres", "sqlite"];
+
+/// Looks up a dialect by name, e.g. `"mysql84"`. `server_version` is
+/// ignored by dialects which don't depend on it, while the others default to
+/// a version of their own.
+pub fn get_dialect(name: &str,
+                   server_version: Option<ServerVersion>)
+                   -> Result<Box<dyn Dialect>, String> {
+    match name
+    {
+        "mysql84" => Ok(Box::new(MySql84)),
+        "mysql80" =>
+        {
+            Ok(Box::new(server_version.map(MySql80).unwrap_or_default()))
+        },
+        "mariadb" =>
+        {
+            Ok(Box::new(server_version.map(MariaDb).unwrap_or_default()))
+        },
+        "postgres" => Ok(Box::new(PostgreSql)),
+        "sqlite" => Ok(Box::new(Sqlite)),
+        _ => Err(format!("unknown dialect `{name}`, expected one of: {}",
+                         DIALECT_NAMES.join(", "))),
+    }
+}
+
+/// A trait supposed to be implemented upon `FinalParserState`.
+///
+/// Unless `pretty_print` is set, the generated code is spliced into the
+/// template, keeping everything outside the directives as is (see
+/// `common::compile_statement`).
+pub trait QueryCompiler {
+    fn generate_code(&mut self,
+                     dialect: &dyn Dialect,
+                     pretty_print: bool)
+                     -> Result<CompiledQueryDescriptor, QueryCompilerError>;
+}
+
+/// A trait supposed to be implemented upon any parsed node. Fails if the
+/// node can't be expressed in the given `dialect`.
+pub trait NodeCompiler {
+    fn generate_code(&self,
+                     dialect: &dyn Dialect)
+                     -> Result<String, QueryCompilerError>;
+}
+
+fn get_all_nodes(state: &mut FinalParserState)
+                 -> Vec<impl Node + NodeCompiler> {
+    // NOTE it should be sufficient to just extend this function in
+    // case further nodes are being introduced. the remaining code
+    // should be sufficiently generic
+    state.combined_result_nodes.clone()
+}
+
+impl QueryCompiler for FinalParserState {
+    fn generate_code(&mut self,
+                     dialect: &dyn Dialect,
+                     pretty_print: bool)
+                     -> Result<CompiledQueryDescriptor, QueryCompilerError>
+    {
+        check_template_syntax(&self.statement, dialect)?;
+        if dialect.numbered_placeholder_style() == PlaceholderStyle::Numeric
+        {
+            // `$1` is as long as `?1`, so that the positions of all nodes
+            // are kept.
+            self.statement = to_numeric_placeholders(&self.statement,
+                                                     dialect.quoting_style());
+        }
+
+        let nodes = get_all_nodes(self);
+        let generate_code = |node: &_| {
+            NodeCompiler::generate_code(node, dialect)
+                .map(|code| embed_into_compound_query(code, dialect))
+        };
+        compile_statement(self, nodes, generate_code, dialect, pretty_print)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codegen::{
+            get_dialect,
+            Dialect,
+            MariaDb,
+            MySql80,
+            MySql84,
+            PostgreSql,
+            ServerVersion,
+            Sqlite,
+        },
+        compile,
+        compile_with_options,
+        error::QueryCompilerError,
+        lex::Span,
+        CompileOptions,
+    };
+
+    #[test]
+    fn directives_are_gated_by_server_version() {
+        let template = "SELECT 1 UNION ALL \
+                        combined_result (SELECT id FROM a) AS $id { \
+                        SELECT a.id, b.x FROM a \
+                        INNER JOIN b ON b.a_id = a.id WHERE a.id = $id }";
+        let old_version = ServerVersion::new(10, 1, 48);
+
+        let err = compile(template, &MariaDb(old_version)).unwrap_err();
+        assert!(matches!(err, QueryCompilerError::UnsupportedByDialect(..)));
+        assert_eq!(Span::new(19, 34), err.span());
+
+        let new_version = ServerVersion::new(10, 2, 2);
+        assert!(compile(template, &MariaDb(new_version)).is_ok());
+
+        let mysql_version = ServerVersion::new(8, 0, 1);
+        assert!(compile(template, &MySql80(mysql_version)).is_ok());
+    }
+
+    #[test]
+    fn hints_and_lateral_identifiers_are_left_to_the_database() {
+        let hint = "SELECT /*+ SeqScan(a) */ a.id FROM a";
+        let identifiers = "SELECT a.lateral, lateral FROM a";
+        for dialect in
+            [&MariaDb::default() as &dyn Dialect, &Sqlite, &PostgreSql]
+        {
+            assert!(compile(hint, dialect).is_ok(), "{dialect}");
+            assert!(compile(identifiers, dialect).is_ok(), "{dialect}");
+        }
+    }
+
+    #[test]
+    fn lateral_joins_of_templates_are_gated_by_server_version() {
+        let template = "SELECT a.id, x.n FROM a, LATERAL (SELECT COUNT(*) \
+                        AS n FROM b WHERE b.a_id = a.id) AS x \
+                        WHERE a.note <> 'lateral'";
+
+        let old_version = ServerVersion::new(8, 0, 13);
+        let err = compile(template, &MySql80(old_version)).unwrap_err();
+        assert_eq!(Span::new(25, 32), err.span());
+        assert_eq!(Some("`LATERAL` derived tables require version 8.0.14 or \
+                         later, declare a newer server version"
+                                                               .into()),
+                   err.help());
+
+        let new_version = ServerVersion::new(8, 0, 14);
+        assert!(compile(template, &MySql80(new_version)).is_ok());
+    }
+
+    #[test]
+    fn dialects_are_looked_up_by_name() {
+        let version = ServerVersion::new(10, 6, 16);
+        let dialect = get_dialect("mariadb", Some(version)).unwrap();
+        assert_eq!("MariaDB 10.6.16", dialect.to_string());
+        assert_eq!("MySQL 8.4",
+                   get_dialect("mysql84", None).unwrap().to_string());
+        assert!(get_dialect("oracle", None).is_err());
+    }
+
+    #[test]
+    fn template_is_kept_outside_directives_unless_pretty_printed() {
+        let template = "SELECT /*+ NO_MERGE(x) */ x.id -- keep\n\
+                        FROM (combined_result (SELECT id FROM a) AS $id { \
+                        SELECT a.id, b.x FROM a \
+                        INNER JOIN b ON b.a_id = a.id WHERE a.id = $id }) \
+                        AS x";
+        let compiled = compile(template, &MySql84).unwrap();
+        assert!(compiled.statement
+                        .starts_with("SELECT /*+ NO_MERGE(x) */ x.id -- keep\n\
+                                      FROM ((WITH RECURSIVE"));
+        assert!(compiled.statement.ends_with(")) AS x"));
 
-pub use mysql84::MySql84QueryCompiler;
+        let options = CompileOptions { pretty_print: true,
+                                       ..CompileOptions::new(&MySql84) };
+        let compiled = compile_with_options(template, &options).unwrap();
+        assert!(compiled.statement.starts_with("SELECT\n  x.id\nFROM"));
+    }
+}
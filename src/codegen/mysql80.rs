@@ -0,0 +1,60 @@
+use {
+    crate::codegen::{Dialect, Feature, ServerVersion, Support},
+    sqlparser::dialect::MySqlDialect,
+    std::fmt,
+};
+
+/// The version of the target server, unless declared otherwise. This is the
+/// first GA release of MySQL 8.0.
+pub const DEFAULT_SERVER_VERSION: ServerVersion = ServerVersion::new(8, 0, 11);
+
+/// MySQL 8.0, given the declared version of the target server.
+///
+/// `combined_result` directives are compiled like for MySQL 8.4, as
+/// `LATERAL` derived tables are only available since MySQL 8.0.14.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MySql80(pub ServerVersion);
+
+impl Default for MySql80 {
+    fn default() -> Self {
+        Self(DEFAULT_SERVER_VERSION)
+    }
+}
+
+impl Dialect for MySql80 {
+    fn support(&self, feature: Feature) -> Support {
+        match feature
+        {
+            Feature::RecursiveCte =>
+            {
+                Support::Since(ServerVersion::new(8, 0, 1))
+            },
+            Feature::LateralJoin =>
+            {
+                Support::Since(ServerVersion::new(8, 0, 14))
+            },
+            Feature::JsonTable => Support::Since(ServerVersion::new(8, 0, 4)),
+            Feature::NestedCte
+            | Feature::OptimizerHints
+            | Feature::ParenthesizedCompoundOperands => Support::Always,
+        }
+    }
+
+    fn server_version(&self) -> Option<ServerVersion> {
+        Some(self.0)
+    }
+
+    fn parser_dialect(&self) -> Box<dyn sqlparser::dialect::Dialect> {
+        Box::new(MySqlDialect {})
+    }
+
+    fn prefers_lateral_joins(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Display for MySql80 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MySQL {}", self.0)
+    }
+}
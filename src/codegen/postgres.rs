@@ -0,0 +1,51 @@
+use {
+    crate::{
+        codegen::{Dialect, Feature, Support},
+        lex::{PlaceholderStyle, QuotingStyle},
+    },
+    sqlparser::dialect::PostgreSqlDialect,
+    std::fmt,
+};
+
+/// PostgreSQL
+///
+/// `combined_result` directives are compiled to `LATERAL` joins. Numbered
+/// placeholders (see `crate::parameters`) are rewritten to `$1`, `$2`, ...,
+/// as PostgreSQL doesn't know `?1`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PostgreSql;
+
+impl Dialect for PostgreSql {
+    fn support(&self, feature: Feature) -> Support {
+        match feature
+        {
+            Feature::RecursiveCte
+            | Feature::NestedCte
+            | Feature::LateralJoin
+            | Feature::ParenthesizedCompoundOperands => Support::Always,
+            Feature::JsonTable | Feature::OptimizerHints => Support::Never,
+        }
+    }
+
+    fn quoting_style(&self) -> QuotingStyle {
+        QuotingStyle::Standard
+    }
+
+    fn parser_dialect(&self) -> Box<dyn sqlparser::dialect::Dialect> {
+        Box::new(PostgreSqlDialect {})
+    }
+
+    fn numbered_placeholder_style(&self) -> PlaceholderStyle {
+        PlaceholderStyle::Numeric
+    }
+
+    fn default_output_placeholder_style(&self) -> Option<PlaceholderStyle> {
+        Some(PlaceholderStyle::Numeric)
+    }
+}
+
+impl fmt::Display for PostgreSql {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PostgreSQL")
+    }
+}
@@ -0,0 +1,44 @@
+use {
+    crate::{
+        codegen::{Dialect, Feature, Support},
+        lex::QuotingStyle,
+    },
+    sqlparser::dialect::SQLiteDialect,
+    std::fmt,
+};
+
+/// SQLite
+///
+/// In contrast to MySQL, SQLite can't parse parenthesized operands of
+/// compound queries (like `(SELECT ...) UNION ALL (SELECT ...)`). Hence,
+/// generated code is wrapped into a plain `SELECT`. Numbered placeholders
+/// (see `crate::parameters`) are kept as is, as SQLite knows `?1` as well.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sqlite;
+
+impl Dialect for Sqlite {
+    fn support(&self, feature: Feature) -> Support {
+        match feature
+        {
+            Feature::RecursiveCte | Feature::NestedCte => Support::Always,
+            Feature::LateralJoin
+            | Feature::JsonTable
+            | Feature::OptimizerHints
+            | Feature::ParenthesizedCompoundOperands => Support::Never,
+        }
+    }
+
+    fn quoting_style(&self) -> QuotingStyle {
+        QuotingStyle::Standard
+    }
+
+    fn parser_dialect(&self) -> Box<dyn sqlparser::dialect::Dialect> {
+        Box::new(SQLiteDialect {})
+    }
+}
+
+impl fmt::Display for Sqlite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SQLite")
+    }
+}
@@ -1,29 +1,377 @@
-use {
-    pyo3::{exceptions::PyValueError, PyErr},
-    sqlparser::parser::ParserError,
-    thiserror::Error,
-};
+use {crate::lex::Span, sqlparser::parser::ParserError, thiserror::Error};
 
 #[derive(Clone, Debug, Error)]
 pub enum QueryCompilerError {
     #[error("expecting `{0}` after keyword `{1}`")]
-    MissingCharacter(char, &'static str),
+    MissingCharacter(char, &'static str, Span),
 
-    #[error("nesting `{0}` within `{1}` is not supported")]
-    UnsupportedNesting(&'static str, &'static str),
+    #[error("nesting `{0}` within `{1}` is not supported here")]
+    UnsupportedNesting(&'static str, &'static str, Span),
 
-    #[error("directive `{0}` at offset `{1}` is incomplete")]
-    DirectiveIncomplete(&'static str, usize),
+    #[error("directive `{0}` is incomplete")]
+    DirectiveIncomplete(&'static str, Span),
+
+    #[error("directive `{0}` has an unclosed parenthese")]
+    UnbalancedParenthese(&'static str, Span),
+
+    /// A directive which can't be expressed on the target dialect, along
+    /// with the requirement which isn't met.
+    #[error("directive `{0}` can't be expressed on {1}")]
+    UnsupportedByDialect(&'static str, String, String, Span),
+
+    /// Syntax of the template (like `LATERAL` derived tables) which the target
+    /// dialect doesn't support, along with the requirement which isn't met.
+    #[error("{0} can't be used on {1}")]
+    UnsupportedSyntax(String, String, String, Span),
+
+    #[error("placeholder `{0}` is invalid")]
+    InvalidPlaceholder(String, Span),
 
     #[error("parsing inner query failed: {0}")]
-    InnerQueryInvalid(String),
+    InnerQueryInvalid(String, Span),
+
+    #[error("resulting query is invalid: {0}")]
+    ResultingQueryInvalid(ParserError, Span),
 
-    #[error("resulting query is invalid: {0}, {1}")]
-    ResultingQueryInvalid(String, ParserError),
+    /// Several errors, ordered by their location. See
+    /// `QueryCompilerError::from_errors`.
+    #[error("template contains {} errors", .0.len())]
+    Multiple(Vec<QueryCompilerError>),
 }
 
-impl From<QueryCompilerError> for PyErr {
-    fn from(value: QueryCompilerError) -> Self {
-        PyValueError::new_err(value.to_string())
+/// Raised when binding parameters to a compiled query fails. See
+/// `CompiledQueryDescriptor::expand_named_parameters`.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum ParameterError {
+    #[error("parameter `{0}` is missing")]
+    MissingParameter(String),
+
+    #[error("placeholders of the template are not named")]
+    NotNamed,
+}
+
+impl QueryCompilerError {
+    /// Constructs an `InnerQueryInvalid` error whose location is not known
+    /// yet. See `QueryCompilerError::locate_within`.
+    pub fn inner_query_invalid(message: impl Into<String>) -> Self {
+        Self::InnerQueryInvalid(message.into(), Span::default())
+    }
+
+    /// Aggregates `errors` into a single error. A single error is returned
+    /// as is, while several errors are ordered by their location.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `errors` is empty.
+    pub fn from_errors(errors: Vec<Self>) -> Self {
+        let mut errors = errors.into_iter()
+                               .flat_map(|err| err.into_errors())
+                               .collect::<Vec<Self>>();
+        errors.sort_by_key(|err| err.span().begin);
+        match errors.len()
+        {
+            0 => panic!("at least one error is required"),
+            1 => errors.remove(0),
+            _ => Self::Multiple(errors),
+        }
+    }
+
+    /// Returns all individual errors, i.e. unpacks `Multiple`.
+    pub fn into_errors(self) -> Vec<Self> {
+        match self
+        {
+            Self::Multiple(errors) => errors,
+            err => vec![err],
+        }
+    }
+
+    /// The span of the template the error refers to. Several errors refer to
+    /// the span of the first one.
+    pub fn span(&self) -> Span {
+        match self
+        {
+            Self::Multiple(errors) =>
+            {
+                errors.first().map_or_else(Span::default, Self::span)
+            },
+            Self::MissingCharacter(.., span)
+            | Self::UnsupportedNesting(.., span)
+            | Self::DirectiveIncomplete(.., span)
+            | Self::UnbalancedParenthese(.., span)
+            | Self::UnsupportedByDialect(.., span)
+            | Self::UnsupportedSyntax(.., span)
+            | Self::InvalidPlaceholder(.., span)
+            | Self::InnerQueryInvalid(.., span)
+            | Self::ResultingQueryInvalid(.., span) => *span,
+        }
+    }
+
+    /// A hint on how to fix the error, if there is one.
+    pub fn help(&self) -> Option<String> {
+        match self
+        {
+            Self::MissingCharacter(character, keyword, _) =>
+            {
+                Some(format!("directive `{keyword}` requires a `{character}` \
+                              at this position"))
+            },
+            Self::UnsupportedNesting(..) =>
+            {
+                let hint =
+                    "directives may only be nested within the `{ ... }` \
+                            block of another directive";
+                Some(hint.into())
+            },
+            Self::DirectiveIncomplete(keyword, _) =>
+            {
+                Some(format!("the expected syntax is `{keyword} (<iteration \
+                              query>) AS $variable {{ <inner query> }}`"))
+            },
+            Self::UnbalancedParenthese(..) =>
+            {
+                let hint =
+                    "add the missing `)` before the `{` of the directive";
+                Some(hint.into())
+            },
+            Self::UnsupportedByDialect(_, _, requirement, _)
+            | Self::UnsupportedSyntax(_, _, requirement, _) =>
+            {
+                Some(requirement.clone())
+            },
+            Self::InvalidPlaceholder(..) =>
+            {
+                Some("numeric placeholders start at `$1`".into())
+            },
+            Self::InnerQueryInvalid(..)
+            | Self::ResultingQueryInvalid(..)
+            | Self::Multiple(..) => None,
+        }
+    }
+
+    /// Attaches `span` to errors which have been raised without knowing their
+    /// location within the template, e.g. while compiling the inner query of
+    /// a node. Errors which already know their location are kept as is.
+    pub fn locate_within(self, span: Span) -> Self {
+        match self
+        {
+            Self::InnerQueryInvalid(message, location)
+                if location == Span::default() =>
+            {
+                Self::InnerQueryInvalid(message, span)
+            },
+            Self::ResultingQueryInvalid(error, location)
+                if location == Span::default() =>
+            {
+                Self::ResultingQueryInvalid(error, span)
+            },
+            Self::Multiple(errors) =>
+            {
+                Self::Multiple(errors.into_iter()
+                                     .map(|err| err.locate_within(span))
+                                     .collect())
+            },
+            other => other,
+        }
+    }
+
+    /// Maps the spans of the error, e.g. when the erroneous statement has
+    /// been derived from the original template.
+    pub fn map_spans(self, f: &impl Fn(Span) -> Span) -> Self {
+        match self
+        {
+            Self::MissingCharacter(character, keyword, span) =>
+            {
+                Self::MissingCharacter(character, keyword, f(span))
+            },
+            Self::UnsupportedNesting(nested, enclosing, span) =>
+            {
+                Self::UnsupportedNesting(nested, enclosing, f(span))
+            },
+            Self::DirectiveIncomplete(keyword, span) =>
+            {
+                Self::DirectiveIncomplete(keyword, f(span))
+            },
+            Self::UnbalancedParenthese(keyword, span) =>
+            {
+                Self::UnbalancedParenthese(keyword, f(span))
+            },
+            Self::UnsupportedByDialect(keyword, dialect, requirement, span) =>
+            {
+                Self::UnsupportedByDialect(keyword,
+                                           dialect,
+                                           requirement,
+                                           f(span))
+            },
+            Self::UnsupportedSyntax(syntax, dialect, requirement, span) =>
+            {
+                Self::UnsupportedSyntax(syntax, dialect, requirement, f(span))
+            },
+            Self::InvalidPlaceholder(placeholder, span) =>
+            {
+                Self::InvalidPlaceholder(placeholder, f(span))
+            },
+            Self::InnerQueryInvalid(message, span) =>
+            {
+                Self::InnerQueryInvalid(message, f(span))
+            },
+            Self::ResultingQueryInvalid(error, span) =>
+            {
+                Self::ResultingQueryInvalid(error, f(span))
+            },
+            Self::Multiple(errors) =>
+            {
+                Self::Multiple(errors.into_iter()
+                                     .map(|err| err.map_spans(f))
+                                     .collect())
+            },
+        }
+    }
+
+    /// Renders the error with a snippet of the `statement` it refers to,
+    /// similar to the diagnostics of `rustc`:
+    ///
+    /// ```text
+    /// error: directive `combined_result` has an unclosed parenthese
+    ///  --> 1:17
+    ///   |
+    /// 1 | combined_result (SELECT id FROM a AS $id {
+    ///   |                 ^
+    ///   = help: add the missing `)` before the `{` of the directive
+    /// ```
+    ///
+    /// Several errors are rendered one after another.
+    pub fn render(&self, statement: &str) -> String {
+        self.render_with_origin(statement, None)
+    }
+
+    /// Like `QueryCompilerError::render`, but the location is prefixed by
+    /// the `origin` of the statement (e.g. a file path), if given.
+    pub fn render_with_origin(&self,
+                              statement: &str,
+                              origin: Option<&str>)
+                              -> String {
+        if let Self::Multiple(errors) = self
+        {
+            return errors.iter()
+                         .map(|err| err.render_with_origin(statement, origin))
+                         .collect::<Vec<String>>()
+                         .join("\n\n");
+        }
+
+        let location = SourceLocation::resolve(statement, self.span().begin);
+        let line = statement.lines()
+                            .nth(location.line - 1)
+                            .unwrap_or_default()
+                            .trim_end_matches('\r');
+        let gutter = " ".repeat(location.line.to_string().len());
+
+        let mut rendered =
+            format!("error: {self}\n{gutter}--> {}{}:{}\n{gutter} |\n{} | \
+                     {line}\n{gutter} | {}",
+                    origin.map_or_else(String::new, |o| format!("{o}:")),
+                    location.line,
+                    location.column,
+                    location.line,
+                    get_caret_line(line, &location, self.span(), statement));
+        if let Some(help) = self.help()
+        {
+            rendered.push_str(&format!("\n{gutter} = help: {help}"));
+        }
+        rendered
+    }
+}
+
+/// A human-readable position within a statement. Both `line` and `column`
+/// start at 1. The column is counted in characters, not in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    pub fn resolve(statement: &str, offset: usize) -> Self {
+        let preceding = &statement[.. offset.min(statement.len())];
+        let line_begin = preceding.rfind('\n').map_or(0, |i| i + 1);
+        Self { line: preceding.matches('\n').count() + 1,
+               column: preceding[line_begin ..].chars().count() + 1 }
+    }
+
+    /// The byte offset of the location within `statement`, i.e. the inverse
+    /// of `SourceLocation::resolve`. Fails if the location is not located
+    /// within `statement`.
+    pub fn to_offset(&self, statement: &str) -> Option<usize> {
+        let line_begin = match self.line
+        {
+            0 => return None,
+            1 => 0,
+            line => statement.match_indices('\n').nth(line - 2)?.0 + 1,
+        };
+        let line = statement[line_begin ..].split('\n').next()?;
+        line.char_indices()
+            .map(|(offset, _)| offset)
+            .chain([line.len()])
+            .nth(self.column.checked_sub(1)?)
+            .map(|offset| line_begin + offset)
+    }
+}
+
+/// Returns the line underlining the `span` within the snippet `line`.
+///
+/// Tabs preceding the span are kept, so that the carets are aligned with the
+/// snippet regardless of the tab width. Spans exceeding the line are cut at
+/// its end.
+fn get_caret_line(line: &str,
+                  location: &SourceLocation,
+                  span: Span,
+                  statement: &str)
+                  -> String {
+    let indentation = line.chars()
+                          .take(location.column - 1)
+                          .map(|c| {
+                              if c == '\t'
+                              {
+                                  '\t'
+                              }
+                              else
+                              {
+                                  ' '
+                              }
+                          })
+                          .collect::<String>();
+    let remaining_characters_in_line =
+        line.chars().count().saturating_sub(location.column - 1);
+    let span_characters = statement[span.begin.min(statement.len())
+                                    .. span.end.min(statement.len())]
+                                                                     .chars()
+                                                                     .count();
+    let carets = span_characters.min(remaining_characters_in_line).max(1);
+    format!("{indentation}{}", "^".repeat(carets))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::QueryCompilerError, lex::Span};
+
+    #[test]
+    fn error_is_rendered_with_snippet() {
+        let statement = "SELECT *\nFROM (combined_result (SELECT id AS $id {";
+        let err = QueryCompilerError::UnbalancedParenthese("combined_result",
+                                                           Span::new(30, 31));
+        assert_eq!("error: directive `combined_result` has an unclosed \
+                    parenthese\n --> 2:22\n  |\n2 | FROM (combined_result \
+                    (SELECT id AS $id {\n  |                      ^\n  = \
+                    help: add the missing `)` before the `{` of the directive",
+                   err.render(statement));
+    }
+
+    #[test]
+    fn carets_cover_span_within_line() {
+        let statement = "\tcombined_result\n{";
+        let err = QueryCompilerError::DirectiveIncomplete("combined_result",
+                                                          Span::new(1, 17));
+        let rendered = err.render(statement);
+        assert!(rendered.contains("\n1 | \tcombined_result\n  | \t^^^^^^^^^\
+                                   ^^^^^^\n"));
     }
 }
@@ -1,6 +1,5 @@
-//! Constants which are used while parsing.
-
-pub const WORD_DELIMITER: &str = " ";
+//! Constants and token types which are used while parsing.
+use std::str::FromStr;
 
 pub const KEYWORD_COMBINED_RESULT: &str = "combined_result";
 
@@ -11,3 +10,153 @@ pub const PARENTHESE_END: char = ')';
 pub const BRACE_START: char = '{';
 pub const BRACE_END: char = '}';
 pub const VARIABLE_START: char = '$';
+pub const PLACEHOLDER: char = '?';
+pub const FORMAT_PLACEHOLDER: &str = "%s";
+pub const PYFORMAT_PLACEHOLDER_START: &str = "%(";
+pub const PYFORMAT_PLACEHOLDER_END: &str = ")s";
+pub const NUMERIC_PLACEHOLDER_START: char = '$';
+pub const NAMED_PLACEHOLDER_START: char = ':';
+
+pub const SINGLE_QUOTE: char = '\'';
+pub const DOUBLE_QUOTE: char = '"';
+pub const BACKTICK: char = '`';
+pub const ESCAPE_CHARACTER: char = '\\';
+pub const ESCAPE_STRING_PREFIX: &str = "E";
+pub const LINE_COMMENT_START: &str = "--";
+pub const HASH_COMMENT_START: char = '#';
+pub const BLOCK_COMMENT_START: &str = "/*";
+pub const BLOCK_COMMENT_END: &str = "*/";
+pub const KEYWORD_FROM: &str = "FROM";
+pub const KEYWORD_JOIN: &str = "JOIN";
+pub const KEYWORD_LATERAL: &str = "LATERAL";
+
+/// A byte range within the scanned statement. `begin` is inclusive,
+/// `end` is exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub begin: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(begin: usize, end: usize) -> Self {
+        Self { begin,
+               end }
+    }
+}
+
+/// The syntax of query parameter placeholders, named after the
+/// `paramstyle`s of PEP 249.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlaceholderStyle {
+    /// `?`
+    #[default]
+    Qmark,
+    /// `%s`
+    Format,
+    /// `$1`, `$2`, ...
+    Numeric,
+    /// `:name`
+    Named,
+    /// `%(name)s`
+    Pyformat,
+}
+
+impl PlaceholderStyle {
+    /// Whether placeholders of this style refer to their parameter by name.
+    pub fn is_named(&self) -> bool {
+        matches!(self, Self::Named | Self::Pyformat)
+    }
+}
+
+impl FromStr for PlaceholderStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value
+        {
+            "qmark" => Ok(Self::Qmark),
+            "format" => Ok(Self::Format),
+            "numeric" => Ok(Self::Numeric),
+            "named" => Ok(Self::Named),
+            "pyformat" => Ok(Self::Pyformat),
+            _ => Err(format!("unknown placeholder style `{value}`")),
+        }
+    }
+}
+
+/// The lexical rules of string literals, quoted identifiers and comments,
+/// which differ between MySQL and standard SQL.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuotingStyle {
+    /// `'single'` and `"double"` quoted string literals with backslash
+    /// escapes, `` `backticked` `` identifiers and `#` comments. `--` only
+    /// starts a comment if followed by whitespace.
+    #[default]
+    MySql,
+    /// `'single'` quoted string literals without backslash escapes (except
+    /// for PostgreSQL's `E'escaped'` strings), `"double"` quoted (or
+    /// `` `backticked` ``) identifiers and `--` comments.
+    Standard,
+}
+
+/// The lexical category of a scanned token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Any sequence of whitespace characters (spaces, tabs, CR, LF, ...).
+    Whitespace,
+    /// A keyword or an identifier, e.g. `combined_result` or `col_a1`.
+    Word,
+    /// A `sqlquerypp` variable, e.g. `$id`.
+    Variable,
+    /// A placeholder of a query parameter, e.g. `?` or `:name`. See
+    /// `PlaceholderStyle`.
+    Placeholder,
+    /// A quoted string literal, including its quotes. See `QuotingStyle`.
+    StringLiteral,
+    /// A quoted identifier, including its quotes. See `QuotingStyle`.
+    QuotedIdentifier,
+    /// A `-- line`, `# line` or `/* block */` comment.
+    Comment,
+    OpeningParenthese,
+    ClosingParenthese,
+    OpeningBrace,
+    ClosingBrace,
+    /// Any other single character, e.g. `,`, `.` or `=`.
+    Punctuation,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, begin: usize, end: usize) -> Self {
+        Self { kind,
+               span: Span::new(begin, end) }
+    }
+
+    /// Whether this token is the structural (i.e. not quoted or commented)
+    /// occurrence of `character`.
+    pub fn is_character(&self, statement: &str, character: char) -> bool {
+        match self.kind
+        {
+            TokenKind::OpeningParenthese
+            | TokenKind::ClosingParenthese
+            | TokenKind::OpeningBrace
+            | TokenKind::ClosingBrace
+            | TokenKind::Punctuation =>
+            {
+                self.text(statement).starts_with(character)
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns the text of this token within the scanned `statement`.
+    pub fn text<'t>(&self, statement: &'t str) -> &'t str {
+        &statement[self.span.begin .. self.span.end]
+    }
+}
@@ -1,57 +1,257 @@
+//! `sqlquerypp` compiles SQL query templates containing `sqlquerypp`
+//! directives (like `combined_result`) to valid, optimized SQL queries.
+//!
+//! The high-level entrypoint is `compile()`:
+//!
+//! ```
+//! use sqlquerypp::{compile, MySql84};
+//!
+//! let template = "combined_result (SELECT id FROM a) AS $id {
+//!                     SELECT a.id, b.name FROM a
+//!                     INNER JOIN b ON b.a_id = a.id
+//!                     WHERE a.id = $id
+//!                 }";
+//! let compiled = compile(template, &MySql84).unwrap();
+//! assert!(compiled.statement.contains("WITH RECURSIVE"));
+//! assert_eq!(1, compiled.combined_result_node_slices.len());
+//! ```
+//!
+//! The Python bindings are built with the `python` feature.
 use {
     crate::{
+        codegen::QueryCompiler,
+        error::QueryCompilerError,
+        lex::Span,
+        parameters::NumberedTemplate,
         parser::ParserState,
+        script::split_script,
         types::{CombinedResultNodeSlice, CompiledQueryDescriptor},
     },
-    pyo3::prelude::*,
+    std::collections::BTreeMap,
 };
 
-mod codegen;
-mod error;
-mod lex;
-mod parser;
-mod scanner;
-mod types;
+pub mod codegen;
+pub mod error;
+pub mod lex;
+pub mod parameters;
+pub mod parser;
+#[cfg(feature = "python")]
+mod python;
+pub mod scanner;
+pub mod script;
+pub mod types;
 
-/// make_compiler_impl
+pub use {
+    codegen::{
+        Dialect,
+        MariaDb,
+        MySql80,
+        MySql84,
+        PostgreSql,
+        ServerVersion,
+        Sqlite,
+    },
+    lex::PlaceholderStyle,
+};
+
+/// Options for compiling a query template. See `compile_with_options`.
+#[derive(Clone, Copy, Debug)]
+pub struct CompileOptions<'d> {
+    pub dialect: &'d dyn Dialect,
+    /// The style of the placeholders within the template.
+    pub placeholder_style: PlaceholderStyle,
+    /// The style of the placeholders within the resulting query.
+    pub output_placeholder_style: PlaceholderStyle,
+    /// Whether the resulting query is reformatted as a whole. Otherwise,
+    /// only the directives are replaced by their generated code, while the
+    /// rest of the template (including comments) is kept byte-for-byte.
+    pub pretty_print: bool,
+}
+
+impl<'d> CompileOptions<'d> {
+    /// Options for compiling templates with `?` placeholders to `dialect`.
+    /// The resulting query has `?` placeholders as well, unless the dialect
+    /// defaults to another style (see
+    /// `Dialect::default_output_placeholder_style`). It isn't pretty
+    /// printed.
+    pub fn new(dialect: &'d dyn Dialect) -> Self {
+        let placeholder_style = PlaceholderStyle::default();
+        Self { dialect,
+               placeholder_style,
+               output_placeholder_style:
+                   dialect.default_output_placeholder_style()
+                          .unwrap_or(placeholder_style),
+               pretty_print: false }
+    }
+}
+
+/// Compiles the query `template` containing `?` placeholders to a valid SQL
+/// query of the given `dialect`.
+pub fn compile(template: &str,
+               dialect: &dyn Dialect)
+               -> Result<CompiledQueryDescriptor, QueryCompilerError> {
+    compile_with_options(template, &CompileOptions::new(dialect))
+}
+
+/// Compiles the query `template` to a valid SQL query.
+///
+/// The placeholders of the resulting query are rewritten to the requested
+/// output style. The `parameter_order` of the result maps them to the
+/// parameters of the template.
+pub fn compile_with_options(
+    template: &str,
+    options: &CompileOptions)
+    -> Result<CompiledQueryDescriptor, QueryCompilerError> {
+    // Placeholders are numbered, so that they can be traced through code
+    // generation. All offsets are mapped back to the original template.
+    let numbered = NumberedTemplate::new(template,
+                                         options.placeholder_style,
+                                         options.dialect.quoting_style())?;
+    let compiled = compile_numbered_template(&numbered.statement,
+                                             options).map_err(|err| {
+                       err.map_spans(&|span| numbered.original_span(span))
+                   })?;
+
+    let combined_result_node_slices =
+        compiled.combined_result_node_slices
+                .into_iter()
+                .map(|slice| {
+                    let span =
+                        numbered.original_span(Span::new(slice.scope_begin,
+                                                         slice.scope_end));
+                    CombinedResultNodeSlice { scope_begin: span.begin,
+                                              scope_end: span.end }
+                })
+                .collect();
+    let (statement, parameter_order) =
+        numbered.resolve_parameter_order(&compiled.statement,
+                                         options.dialect
+                                                .numbered_placeholder_style(),
+                                         options.output_placeholder_style);
+    let parameter_positions =
+        numbered.get_parameter_positions(&parameter_order);
+    Ok(CompiledQueryDescriptor { statement,
+                                 combined_result_node_slices,
+                                 parameter_order,
+                                 parameter_positions })
+}
+
+/// Compiles each `;`-separated statement of the `script` independently, see
+/// `script::split_script`.
+///
+/// The parameters of the script are shared by all of its statements, i.e.
+/// the `parameter_order` of each compiled statement refers to the
+/// parameters of the entire script: positional parameters are numbered in
+/// order of their placeholders within the script, named parameters in order
+/// of the first occurrence of their name within the script. The slices of
+/// the nodes refer to the entire script as well. Errors of all statements
+/// are collected.
+pub fn compile_script(
+    script: &str,
+    options: &CompileOptions)
+    -> Result<Vec<CompiledQueryDescriptor>, QueryCompilerError> {
+    let mut compiled_statements = vec![];
+    let mut parameter_names = vec![];
+    let mut errors = vec![];
+    let statements = split_script(script,
+                                  options.placeholder_style,
+                                  options.dialect.quoting_style());
+    for statement in statements
+    {
+        let offset = statement.span.begin;
+        match compile_with_options(statement.text(script), options)
+        {
+            Ok(mut compiled) =>
+            {
+                for slice in compiled.combined_result_node_slices.iter_mut()
+                {
+                    slice.scope_begin += offset;
+                    slice.scope_end += offset;
+                }
+                match options.placeholder_style.is_named()
+                {
+                    true => number_parameters_by_name(&mut compiled,
+                                                      &mut parameter_names),
+                    false =>
+                    {
+                        for parameter in compiled.parameter_order.iter_mut()
+                        {
+                            *parameter += statement.parameter_offset;
+                        }
+                    },
+                }
+                compiled_statements.push(compiled);
+            },
+            Err(err) => errors.push(err.map_spans(&|span| {
+                                           Span::new(span.begin + offset,
+                                                     span.end + offset)
+                                       })),
+        }
+    }
+
+    if !errors.is_empty()
+    {
+        return Err(QueryCompilerError::from_errors(errors));
+    }
+    Ok(compiled_statements)
+}
+
+/// Renumbers the named parameters of a compiled statement of a script by
+/// the `parameter_names` of the script, which are extended by the names
+/// occurring for the first time.
 ///
-/// This is a shorthand for generating a high-level compiler function.
-macro_rules! make_compiler_impl {
-    ($func_name:ident, $trait:ty) => {
-        #[pyfunction]
-        fn $func_name(statement: String) -> PyResult<CompiledQueryDescriptor> {
-            use $trait;
-
-            // First, we construct the parser. See ParserState.
-            let mut parser = ParserState::initialize(&statement);
-
-            // After that, we do all the lexical checks and parsing systematics.
-            // The parser now contains a
-            parser.parse()?;
-
-            // When parsing, the parser deals with "intermediate structs" which
-            // means, those intermediates heavily make use of "std::Option".
-            // For the final code generation, it is not much helpful to always
-            // have to check whether the parsed objects are complete.
-            // This is what the separate state and the separate `Complete...`
-            // datastructs are for. See `FinalParserState`.
-            let mut finalized_state = parser.finalize()?;
-
-            Ok(finalized_state.generate_code()?)
+/// Within the statement, parameters are numbered in order of the first
+/// occurrence of their name (see `NumberedTemplate`), so that the names are
+/// appended in order of their first occurrence within the script.
+fn number_parameters_by_name(compiled: &mut CompiledQueryDescriptor,
+                             parameter_names: &mut Vec<String>) {
+    let mut names_by_index = BTreeMap::new();
+    for (name, positions) in compiled.parameter_positions.iter()
+    {
+        for position in positions.iter()
+        {
+            names_by_index.insert(compiled.parameter_order[*position], name);
+        }
+    }
+
+    let mut script_indices = BTreeMap::new();
+    for (index, name) in names_by_index
+    {
+        let script_index = parameter_names.iter()
+                                          .position(|known| known == name)
+                                          .unwrap_or(parameter_names.len());
+        if script_index == parameter_names.len()
+        {
+            parameter_names.push(name.clone());
         }
-    };
+        script_indices.insert(index, script_index);
+    }
+    for parameter in compiled.parameter_order.iter_mut()
+    {
+        *parameter = script_indices[parameter];
+    }
 }
 
-make_compiler_impl!(preprocess_mysql84_query, codegen::MySql84QueryCompiler);
+fn compile_numbered_template(
+    statement: &String,
+    options: &CompileOptions)
+    -> Result<CompiledQueryDescriptor, QueryCompilerError> {
+    // First, we construct the parser. See ParserState.
+    let mut parser = ParserState::initialize(statement)
+        .with_quoting_style(options.dialect.quoting_style());
 
-/// Constructs the (internal!) sqlquerypp module containing helper
-/// datastructs and compiler implementations.
-#[pymodule]
-fn sqlquerypp(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(preprocess_mysql84_query, m)?)?;
+    // After that, we do all the lexical checks and parsing systematics.
+    parser.parse()?;
 
-    m.add_class::<CompiledQueryDescriptor>()?;
-    m.add_class::<CombinedResultNodeSlice>()?;
+    // When parsing, the parser deals with "intermediate structs" which
+    // means, those intermediates heavily make use of "std::Option".
+    // For the final code generation, it is not much helpful to always
+    // have to check whether the parsed objects are complete.
+    // This is what the separate state and the separate `Complete...`
+    // datastructs are for. See `FinalParserState`.
+    let mut finalized_state = parser.finalize()?;
 
-    Ok(())
+    QueryCompiler::generate_code(&mut finalized_state,
+                                 options.dialect,
+                                 options.pretty_print)
 }
@@ -0,0 +1,426 @@
+//! Command line interface of `sqlquerypp`. Compiles query templates without
+//! the need for the Python API, e.g. for debugging templates or for checking
+//! them within pre-commit hooks.
+use {
+    clap::{
+        builder::PossibleValuesParser,
+        Args,
+        Parser,
+        Subcommand,
+        ValueEnum,
+    },
+    serde_json::json,
+    sqlquerypp::{
+        codegen::{get_dialect, DIALECT_NAMES},
+        compile_script,
+        compile_with_options,
+        error::QueryCompilerError,
+        types::CompiledQueryDescriptor,
+        CompileOptions,
+        Dialect,
+        PlaceholderStyle,
+        ServerVersion,
+    },
+    std::{
+        fs,
+        io::{self, Read, Write},
+        path::{Path, PathBuf},
+        process::ExitCode,
+    },
+};
+
+/// The file extension of query templates, see `sqlquerypp check`.
+const TEMPLATE_EXTENSION: &str = "sqlpp";
+
+#[derive(Parser)]
+#[command(version, about = "SQL query preprocessor")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compiles a query template and prints the resulting query.
+    Compile {
+        /// The template to compile. Reads from stdin if omitted or `-`.
+        template: Option<PathBuf>,
+
+        #[command(flatten)]
+        options: CompilerOptions,
+
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = Format::Sql)]
+        format: Format,
+    },
+
+    /// Compiles all templates and reports their errors.
+    ///
+    /// Directories are searched recursively for templates (`*.sqlpp`). Exits
+    /// with a non-zero status if any template fails to compile.
+    Check {
+        /// Templates or directories which are searched recursively.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        #[command(flatten)]
+        options: CompilerOptions,
+    },
+}
+
+#[derive(Args)]
+struct CompilerOptions {
+    /// The SQL dialect to compile to.
+    #[arg(long,
+          default_value = DIALECT_NAMES[0],
+          value_parser = PossibleValuesParser::new(DIALECT_NAMES))]
+    dialect: String,
+
+    /// The version of the target server, e.g. `8.0.35`. Only applies to
+    /// MySQL 8.0 (defaults to 8.0.11) and MariaDB (defaults to 10.11.2).
+    #[arg(long)]
+    server_version: Option<ServerVersion>,
+
+    /// The style of the placeholders within templates.
+    #[arg(long, value_enum, default_value_t = PlaceholderStyleArg::Qmark)]
+    placeholder_style: PlaceholderStyleArg,
+
+    /// The style of the placeholders within the resulting query. Defaults to
+    /// `numeric` for PostgreSQL and to the style of the placeholders within
+    /// templates otherwise.
+    #[arg(long, value_enum)]
+    output_placeholder_style: Option<PlaceholderStyleArg>,
+
+    /// Reformats the resulting queries as a whole. Otherwise, only the
+    /// directives are replaced, keeping the rest of the templates as is.
+    #[arg(long)]
+    pretty: bool,
+
+    /// Compiles each `;`-separated statement of the templates independently.
+    #[arg(long)]
+    script: bool,
+}
+
+impl CompilerOptions {
+    fn dialect(&self) -> Box<dyn Dialect> {
+        get_dialect(&self.dialect, self.server_version)
+            .expect("dialect names are validated while parsing arguments")
+    }
+
+    fn to_compile_options<'d>(&self,
+                              dialect: &'d dyn Dialect)
+                              -> CompileOptions<'d> {
+        let placeholder_style = self.placeholder_style.into();
+        let output_placeholder_style =
+            self.output_placeholder_style
+                .map(Into::into)
+                .or(dialect.default_output_placeholder_style())
+                .unwrap_or(placeholder_style);
+        CompileOptions { placeholder_style,
+                         output_placeholder_style,
+                         pretty_print: self.pretty,
+                         ..CompileOptions::new(dialect) }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PlaceholderStyleArg {
+    /// `?`
+    Qmark,
+    /// `%s`
+    Format,
+    /// `$1`, `$2`, ...
+    Numeric,
+    /// `:name`
+    Named,
+    /// `%(name)s`
+    Pyformat,
+}
+
+impl From<PlaceholderStyleArg> for PlaceholderStyle {
+    fn from(value: PlaceholderStyleArg) -> Self {
+        match value
+        {
+            PlaceholderStyleArg::Qmark => PlaceholderStyle::Qmark,
+            PlaceholderStyleArg::Format => PlaceholderStyle::Format,
+            PlaceholderStyleArg::Numeric => PlaceholderStyle::Numeric,
+            PlaceholderStyleArg::Named => PlaceholderStyle::Named,
+            PlaceholderStyleArg::Pyformat => PlaceholderStyle::Pyformat,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// The resulting query only.
+    Sql,
+    /// A JSON object containing the resulting query, the slices of all
+    /// compiled directives and the parameter order. Scripts result in an
+    /// array of such objects.
+    Json,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command
+    {
+        Command::Compile { template,
+                           options,
+                           format, } =>
+        {
+            let dialect = options.dialect();
+            compile_template(template,
+                             &options.to_compile_options(dialect.as_ref()),
+                             options.script,
+                             format)
+        },
+        Command::Check { paths,
+                         options, } =>
+        {
+            let dialect = options.dialect();
+            check_templates(&paths,
+                            &options.to_compile_options(dialect.as_ref()),
+                            options.script)
+        },
+    };
+
+    result.unwrap_or_else(|err| {
+              eprintln!("error: {err}");
+              ExitCode::FAILURE
+          })
+}
+
+fn compile_template(template: Option<PathBuf>,
+                    options: &CompileOptions,
+                    script: bool,
+                    format: Format)
+                    -> io::Result<ExitCode> {
+    let (origin, statement) = match template
+    {
+        Some(path) if path != Path::new("-") =>
+        {
+            (path.display().to_string(), fs::read_to_string(path)?)
+        },
+        _ =>
+        {
+            let mut statement = String::new();
+            io::stdin().read_to_string(&mut statement)?;
+            ("<stdin>".into(), statement)
+        },
+    };
+
+    match compile(&statement, options, script)
+    {
+        Ok(compiled) =>
+        {
+            writeln!(io::stdout(), "{}", to_output(&compiled, script, format))?;
+            Ok(ExitCode::SUCCESS)
+        },
+        Err(err) =>
+        {
+            eprintln!("{}", err.render_with_origin(&statement, Some(&origin)));
+            Ok(ExitCode::FAILURE)
+        },
+    }
+}
+
+fn check_templates(paths: &[PathBuf],
+                   options: &CompileOptions,
+                   script: bool)
+                   -> io::Result<ExitCode> {
+    let mut templates = vec![];
+    for path in paths
+    {
+        collect_templates(path, &mut templates)?;
+    }
+    templates.sort();
+
+    let mut failed = 0;
+    for template in templates.iter()
+    {
+        let statement = fs::read_to_string(template)?;
+        if let Err(err) = compile(&statement, options, script)
+        {
+            let origin = template.display().to_string();
+            eprintln!("{}\n",
+                      err.render_with_origin(&statement, Some(&origin)));
+            failed += 1;
+        }
+    }
+
+    eprintln!("checked {} templates, {failed} failed", templates.len());
+    match failed
+    {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// Compiles `statement` as a single query or, if `script` is set, as a script
+/// of `;`-separated statements.
+fn compile(statement: &str,
+           options: &CompileOptions,
+           script: bool)
+           -> Result<Vec<CompiledQueryDescriptor>, QueryCompilerError> {
+    match script
+    {
+        true => compile_script(statement, options),
+        false => compile_with_options(statement, options).map(|c| vec![c]),
+    }
+}
+
+/// Collects all templates within `path`. Directories are searched
+/// recursively, while explicitly given files are collected regardless of
+/// their extension.
+fn collect_templates(path: &Path,
+                     templates: &mut Vec<PathBuf>)
+                     -> io::Result<()> {
+    if !path.is_dir()
+    {
+        templates.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(path)?
+    {
+        let entry_path = entry?.path();
+        if entry_path.is_dir()
+        {
+            collect_templates(&entry_path, templates)?;
+        }
+        else if entry_path.extension()
+                            .is_some_and(|ext| ext == TEMPLATE_EXTENSION)
+        {
+            templates.push(entry_path);
+        }
+    }
+    Ok(())
+}
+
+/// Renders the compiled statements of a template. Statements of scripts are
+/// terminated by `;` or, within JSON, listed in an array.
+fn to_output(compiled: &[CompiledQueryDescriptor],
+             script: bool,
+             format: Format)
+             -> String {
+    match (format, script)
+    {
+        (Format::Sql, false) => compiled[0].statement.clone(),
+        (Format::Sql, true) =>
+        {
+            compiled.iter()
+                    .map(|compiled| format!("{};", compiled.statement))
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+        },
+        (Format::Json, false) => to_json(&compiled[0]).to_string(),
+        (Format::Json, true) => compiled.iter()
+                                        .map(to_json)
+                                        .collect::<serde_json::Value>()
+                                        .to_string(),
+    }
+}
+
+fn to_json(compiled: &CompiledQueryDescriptor) -> serde_json::Value {
+    let slices = compiled.combined_result_node_slices
+                         .iter()
+                         .map(|slice| {
+                             json!({ "scope_begin": slice.scope_begin,
+                                     "scope_end": slice.scope_end })
+                         })
+                         .collect::<Vec<_>>();
+    json!({ "statement": compiled.statement,
+            "combined_result_node_slices": slices,
+            "parameter_order": compiled.parameter_order,
+            "parameter_positions": compiled.parameter_positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{collect_templates, to_json, to_output, Cli, Command, Format},
+        clap::Parser,
+        serde_json::json,
+        sqlquerypp::types::{CombinedResultNodeSlice, CompiledQueryDescriptor},
+        std::{collections::BTreeMap, fs},
+    };
+
+    #[test]
+    fn json_contains_node_slices_and_parameters() {
+        let slice = CombinedResultNodeSlice { scope_begin: 3,
+                                              scope_end: 14 };
+        let compiled =
+            CompiledQueryDescriptor { statement: "SELECT ?, ?".into(),
+                                      combined_result_node_slices:
+                                          vec![slice],
+                                      parameter_order: vec![0, 0],
+                                      parameter_positions:
+                                          BTreeMap::from([("x".into(),
+                                                           vec![0, 1])]) };
+        assert_eq!(json!({ "statement": "SELECT ?, ?",
+                           "combined_result_node_slices": [
+                               { "scope_begin": 3, "scope_end": 14 }
+                           ],
+                           "parameter_order": [0, 0],
+                           "parameter_positions": { "x": [0, 1] } }),
+                   to_json(&compiled));
+    }
+
+    #[test]
+    fn dialects_are_looked_up_by_name() {
+        let cli = Cli::try_parse_from(["sqlquerypp",
+                                       "check",
+                                       "--dialect",
+                                       "mariadb",
+                                       "--server-version",
+                                       "10.6.16",
+                                       "."]).unwrap();
+        let Command::Check { options, .. } = cli.command
+        else
+        {
+            panic!("expected the check command");
+        };
+        assert_eq!("MariaDB 10.6.16", options.dialect().to_string());
+
+        assert!(Cli::try_parse_from(["sqlquerypp",
+                                     "check",
+                                     "--dialect",
+                                     "oracle",
+                                     "."]).is_err());
+    }
+
+    #[test]
+    fn statements_of_scripts_are_terminated() {
+        let compiled =
+            ["SELECT 1", "SELECT 2"].map(|statement| {
+                CompiledQueryDescriptor { statement: statement.into(),
+                                          combined_result_node_slices: vec![],
+                                          parameter_order: vec![],
+                                          parameter_positions: BTreeMap::new() }
+            });
+        assert_eq!("SELECT 1", to_output(&compiled[.. 1], false, Format::Sql));
+        assert_eq!("SELECT 1;\n\nSELECT 2;",
+                   to_output(&compiled, true, Format::Sql));
+        let json = to_output(&compiled, true, Format::Json);
+        assert!(json.starts_with("[{") && json.ends_with("}]"));
+    }
+
+    #[test]
+    fn templates_are_collected_recursively() {
+        let root = std::env::temp_dir().join(format!("sqlquerypp-cli-{}",
+                                                     std::process::id()));
+        fs::create_dir_all(root.join("nested")).unwrap();
+        fs::write(root.join("a.sqlpp"), "").unwrap();
+        fs::write(root.join("b.sql"), "").unwrap();
+        fs::write(root.join("nested").join("c.sqlpp"), "").unwrap();
+
+        let mut templates = vec![];
+        collect_templates(&root, &mut templates).unwrap();
+        templates.sort();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(vec![root.join("a.sqlpp"),
+                        root.join("nested").join("c.sqlpp")],
+                   templates);
+    }
+}
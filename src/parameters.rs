@@ -0,0 +1,374 @@
+//! Tracing of query parameters through code generation.
+//!
+//! Code generation may duplicate parts of the template, including their
+//! placeholders. In order to know which parameter each placeholder of the
+//! resulting query binds, the placeholders of the template are numbered
+//! before compiling (`?` becomes `?1`, `?2`, ...). The numbers are kept by
+//! code generation and resolved afterwards, see
+//! `NumberedTemplate::resolve_parameter_order`.
+use {
+    crate::{error::QueryCompilerError, lex::*, scanner::Scanner},
+    std::collections::BTreeMap,
+};
+
+/// A template whose placeholders have been numbered by the parameter they
+/// refer to.
+///
+/// Positional placeholders (`?` and `%s`) are numbered in order of their
+/// occurrence. Numeric placeholders keep their number, while named
+/// placeholders are numbered in order of the first occurrence of their name.
+pub struct NumberedTemplate {
+    pub statement: String,
+    /// The names of all parameters, if placeholders of the template are
+    /// named. Indexed by the (zero-based) parameter number.
+    pub parameter_names: Vec<String>,
+    /// The spans of all placeholders within the numbered statement, along
+    /// with their spans within the original template.
+    placeholders: Vec<(Span, Span)>,
+    quoting_style: QuotingStyle,
+}
+
+impl NumberedTemplate {
+    pub fn new(template: &str,
+               style: PlaceholderStyle,
+               quoting_style: QuotingStyle)
+               -> Result<Self, QueryCompilerError> {
+        let mut numbered = Self { statement:
+                                      String::with_capacity(template.len()),
+                                  parameter_names: vec![],
+                                  placeholders: vec![],
+                                  quoting_style };
+        let scanner = Scanner::new(template).with_placeholder_style(style)
+                                            .with_quoting_style(quoting_style);
+        for token in scanner
+        {
+            if token.kind == TokenKind::Placeholder
+            {
+                let index =
+                    numbered.get_parameter_index(&token, template, style)?;
+                let begin = numbered.statement.len();
+                numbered.statement
+                        .push_str(&format!("{PLACEHOLDER}{}", index + 1));
+                numbered.placeholders
+                        .push((Span::new(begin, numbered.statement.len()),
+                               token.span));
+            }
+            else
+            {
+                numbered.statement.push_str(token.text(template));
+            }
+        }
+        Ok(numbered)
+    }
+
+    /// Returns the (zero-based) index of the parameter the placeholder
+    /// `token` refers to.
+    fn get_parameter_index(&mut self,
+                           token: &Token,
+                           template: &str,
+                           style: PlaceholderStyle)
+                           -> Result<usize, QueryCompilerError> {
+        let text = token.text(template);
+        match style
+        {
+            PlaceholderStyle::Qmark | PlaceholderStyle::Format =>
+            {
+                Ok(self.placeholders.len())
+            },
+            PlaceholderStyle::Numeric =>
+            {
+                let number = text[NUMERIC_PLACEHOLDER_START.len_utf8() ..]
+                    .parse::<usize>()
+                    .unwrap_or_default();
+                number.checked_sub(1).ok_or_else(|| {
+                    QueryCompilerError::InvalidPlaceholder(text.into(),
+                                                           token.span)
+                })
+            },
+            PlaceholderStyle::Named | PlaceholderStyle::Pyformat =>
+            {
+                let name = get_parameter_name(text, style);
+                let index = self.parameter_names
+                                .iter()
+                                .position(|known| known == name)
+                                .unwrap_or(self.parameter_names.len());
+                if index == self.parameter_names.len()
+                {
+                    self.parameter_names.push(name.into());
+                }
+                Ok(index)
+            },
+        }
+    }
+
+    /// Maps a `span` of the numbered statement to the original template.
+    pub fn original_span(&self, span: Span) -> Span {
+        Span::new(self.original_offset(span.begin),
+                  self.original_offset(span.end))
+    }
+
+    fn original_offset(&self, offset: usize) -> usize {
+        let mut original_offset = offset;
+        for (numbered, original) in self.placeholders.iter()
+        {
+            if offset >= numbered.end
+            {
+                original_offset = offset - numbered.end + original.end;
+            }
+            else if offset > numbered.begin
+            {
+                return original.begin;
+            }
+            else
+            {
+                break;
+            }
+        }
+        original_offset
+    }
+
+    /// Replaces the numbered placeholders of a compiled `statement` by
+    /// placeholders of the given `style`. Returns the resulting statement
+    /// along with the (zero-based) index of the template parameter which
+    /// each of its placeholders binds.
+    ///
+    /// The numbered placeholders of `statement` are either `?1`, `?2`, ...
+    /// or, if `numbered_style` is `PlaceholderStyle::Numeric`, `$1`, `$2`,
+    /// ... (see `to_numeric_placeholders`).
+    ///
+    /// Numeric placeholders are numbered by their position within the
+    /// resulting statement. Named placeholders keep the name of their
+    /// parameter, while positional parameters are named `p1`, `p2`, ...
+    pub fn resolve_parameter_order(&self,
+                                   statement: &str,
+                                   numbered_style: PlaceholderStyle,
+                                   style: PlaceholderStyle)
+                                   -> (String, Vec<usize>) {
+        let mut resolved = String::with_capacity(statement.len());
+        let mut parameter_order = vec![];
+        let scanner =
+            Scanner::new(statement).with_placeholder_style(numbered_style)
+                                   .with_quoting_style(self.quoting_style);
+        for token in scanner
+        {
+            let text = token.text(statement);
+            match get_placeholder_number(&token, text)
+            {
+                Some(number) =>
+                {
+                    parameter_order.push(number - 1);
+                    let position = parameter_order.len();
+                    let placeholder =
+                        self.render_placeholder(number - 1, position, style);
+                    resolved.push_str(&placeholder);
+                },
+                None => resolved.push_str(text),
+            }
+        }
+        (resolved, parameter_order)
+    }
+
+    /// Returns the positions of all placeholders within a resolved statement
+    /// by the name of the parameter they bind. See `resolve_parameter_order`.
+    pub fn get_parameter_positions(&self,
+                                   parameter_order: &[usize])
+                                   -> BTreeMap<String, Vec<usize>> {
+        let mut positions = BTreeMap::<String, Vec<usize>>::new();
+        for (position, index) in parameter_order.iter().enumerate()
+        {
+            if let Some(name) = self.parameter_names.get(*index)
+            {
+                positions.entry(name.clone()).or_default().push(position);
+            }
+        }
+        positions
+    }
+
+    fn render_placeholder(&self,
+                          index: usize,
+                          position: usize,
+                          style: PlaceholderStyle)
+                          -> String {
+        let name = self.parameter_names
+                       .get(index)
+                       .cloned()
+                       .unwrap_or_else(|| format!("p{}", index + 1));
+        match style
+        {
+            PlaceholderStyle::Qmark => PLACEHOLDER.into(),
+            PlaceholderStyle::Format => FORMAT_PLACEHOLDER.into(),
+            PlaceholderStyle::Numeric =>
+            {
+                format!("{NUMERIC_PLACEHOLDER_START}{position}")
+            },
+            PlaceholderStyle::Named =>
+            {
+                format!("{NAMED_PLACEHOLDER_START}{name}")
+            },
+            PlaceholderStyle::Pyformat =>
+            {
+                format!("{PYFORMAT_PLACEHOLDER_START}{name}\
+                         {PYFORMAT_PLACEHOLDER_END}")
+            },
+        }
+    }
+}
+
+/// Rewrites the numbered placeholders of `statement` (like `?1`) to numeric
+/// placeholders (like `$1`), for dialects which can't parse the former.
+pub fn to_numeric_placeholders(statement: &str,
+                               quoting_style: QuotingStyle)
+                               -> String {
+    let mut converted = String::with_capacity(statement.len());
+    for token in Scanner::new(statement).with_quoting_style(quoting_style)
+    {
+        let text = token.text(statement);
+        match get_placeholder_number(&token, text)
+        {
+            Some(number) =>
+            {
+                converted.push_str(&format!("{NUMERIC_PLACEHOLDER_START}{number}"))
+            },
+            None => converted.push_str(text),
+        }
+    }
+    converted
+}
+
+/// Returns the number of a numbered placeholder `token`, e.g. 1 for `?1` or
+/// `$1`.
+fn get_placeholder_number(token: &Token, text: &str) -> Option<usize> {
+    match token.kind
+    {
+        // Both `?` and `$` are a single byte.
+        TokenKind::Placeholder => text[1 ..].parse().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the parameter name of a named placeholder.
+fn get_parameter_name(placeholder: &str, style: PlaceholderStyle) -> &str {
+    match style
+    {
+        PlaceholderStyle::Pyformat =>
+        {
+            &placeholder[PYFORMAT_PLACEHOLDER_START.len()
+                         .. placeholder.len() - PYFORMAT_PLACEHOLDER_END.len()]
+        },
+        _ => &placeholder[NAMED_PLACEHOLDER_START.len_utf8() ..],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::QueryCompilerError,
+        lex::{PlaceholderStyle, QuotingStyle, Span},
+        parameters::{to_numeric_placeholders, NumberedTemplate},
+    };
+
+    fn number(template: &str, style: PlaceholderStyle) -> NumberedTemplate {
+        NumberedTemplate::new(template, style, QuotingStyle::MySql).unwrap()
+    }
+
+    #[test]
+    fn placeholders_are_numbered_in_order() {
+        let numbered = number("a = ? AND b = '?' AND c IN (?, ?)",
+                              PlaceholderStyle::Qmark);
+        assert_eq!("a = ?1 AND b = '?' AND c IN (?2, ?3)", numbered.statement);
+    }
+
+    #[test]
+    fn placeholders_of_all_styles_are_numbered() {
+        let format = number("a = %s AND b LIKE '%s' AND c = ?",
+                            PlaceholderStyle::Format);
+        assert_eq!("a = ?1 AND b LIKE '%s' AND c = ?", format.statement);
+
+        let numeric = number("a = $2 AND b = $1 AND c = $2 AND d = $id",
+                             PlaceholderStyle::Numeric);
+        assert_eq!("a = ?2 AND b = ?1 AND c = ?2 AND d = $id",
+                   numeric.statement);
+
+        let named = number("a = :x AND b = :y AND c = :x AND d::int = 1",
+                           PlaceholderStyle::Named);
+        assert_eq!("a = ?1 AND b = ?2 AND c = ?1 AND d::int = 1",
+                   named.statement);
+        assert_eq!(vec!["x", "y"], named.parameter_names);
+
+        let pyformat = number("a = %(x)s AND b = %(y)s AND c = %(x)s",
+                              PlaceholderStyle::Pyformat);
+        assert_eq!("a = ?1 AND b = ?2 AND c = ?1", pyformat.statement);
+    }
+
+    #[test]
+    fn numeric_placeholders_start_at_one() {
+        let err = NumberedTemplate::new("a = $0",
+                                        PlaceholderStyle::Numeric,
+                                        QuotingStyle::MySql).err()
+                                                            .unwrap();
+        assert!(matches!(err, QueryCompilerError::InvalidPlaceholder(..)));
+        assert_eq!(Span::new(4, 6), err.span());
+    }
+
+    #[test]
+    fn spans_are_mapped_to_original_template() {
+        let numbered = number("? ? x", PlaceholderStyle::Qmark);
+        assert_eq!(Span::new(0, 1), numbered.original_span(Span::new(0, 2)));
+        assert_eq!(Span::new(4, 5), numbered.original_span(Span::new(6, 7)));
+    }
+
+    #[test]
+    fn duplicated_placeholders_are_resolved() {
+        let numbered = number("?, ?, ?", PlaceholderStyle::Qmark);
+        assert_eq!(("?, ?, '?1', ?, ?".to_string(), vec![1, 0, 1, 2]),
+                   numbered.resolve_parameter_order("?2, ?1, '?1', ?2, ?3",
+                                                    PlaceholderStyle::Qmark,
+                                                    PlaceholderStyle::Qmark));
+    }
+
+    #[test]
+    fn numeric_placeholders_are_resolved() {
+        let numbered = number("?, ?", PlaceholderStyle::Qmark);
+        let statement =
+            to_numeric_placeholders("?2, ?1, '?1', $id", QuotingStyle::MySql);
+        assert_eq!("$2, $1, '?1', $id", statement);
+        assert_eq!(("?, ?, '?1', $id".to_string(), vec![1, 0]),
+                   numbered.resolve_parameter_order(&statement,
+                                                    PlaceholderStyle::Numeric,
+                                                    PlaceholderStyle::Qmark));
+    }
+
+    #[test]
+    fn positions_of_named_parameters_are_collected() {
+        let named = number(":x, :y", PlaceholderStyle::Named);
+        let positions = named.get_parameter_positions(&[1, 0, 1]);
+        assert_eq!(vec![1], positions["x"]);
+        assert_eq!(vec![0, 2], positions["y"]);
+
+        let positional = number("?, ?", PlaceholderStyle::Qmark);
+        assert!(positional.get_parameter_positions(&[1, 0, 1]).is_empty());
+    }
+
+    #[test]
+    fn placeholders_are_resolved_to_requested_style() {
+        let positional = number("?, ?", PlaceholderStyle::Qmark);
+        let named = number(":x, :y", PlaceholderStyle::Named);
+        let statement = "?2, ?1, ?2";
+
+        let resolve = |numbered: &NumberedTemplate, style| {
+            numbered.resolve_parameter_order(statement,
+                                             PlaceholderStyle::Qmark,
+                                             style)
+                    .0
+        };
+        assert_eq!("%s, %s, %s",
+                   resolve(&positional, PlaceholderStyle::Format));
+        assert_eq!("$1, $2, $3",
+                   resolve(&positional, PlaceholderStyle::Numeric));
+        assert_eq!(":p2, :p1, :p2",
+                   resolve(&positional, PlaceholderStyle::Named));
+        assert_eq!(":y, :x, :y", resolve(&named, PlaceholderStyle::Named));
+        assert_eq!("%(y)s, %(x)s, %(y)s",
+                   resolve(&named, PlaceholderStyle::Pyformat));
+    }
+}
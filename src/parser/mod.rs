@@ -9,7 +9,11 @@ pub use {
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::{nodes::CompleteCombinedResultNode, ParserState};
+    use crate::{
+        error::QueryCompilerError,
+        lex::Span,
+        parser::{nodes::CompleteCombinedResultNode, ParserState},
+    };
 
     fn get_combined_result_nodes(query: &str)
                                  -> Vec<CompleteCombinedResultNode> {
@@ -53,12 +57,13 @@ mod tests {
                     57,
                     371,
                     "SELECT col_a1 FROM table_a".to_string(),
-                    "$id_a".to_string(),
+                    vec!["$id_a".into()],
                     111,
                     "SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2 FROM table_a a
                      INNER JOIN table_b b
                      ON b.col_a1 = a.col_a1 AND b.cond1 = %s AND b.cond2 = %s
                      WHERE a.col_a1 = $id_a".to_string(),
+                    0,
                 ),
             ],
             nodes,
@@ -92,26 +97,224 @@ mod tests {
                     57,
                     371,
                     "SELECT col_a1 FROM table_a".to_string(),
-                    "$id_a".to_string(),
+                    vec!["$id_a".into()],
                     111,
                     "SELECT a.col_a1, a.col_a2, b.col_b1, b.col_b2 FROM table_a a
                     INNER JOIN table_b b
                     ON b.col_a1 = a.col_a1 AND b.cond1 = %s AND b.cond2 = %s
-                    WHERE a.col_a1 = $id_a".to_string()
+                    WHERE a.col_a1 = $id_a".to_string(),
+                    0,
                 ),
                 CompleteCombinedResultNode::new(
                     415,
                     727,
                     "SELECT col_z1 FROM table_z".to_string(),
-                    "$id_z".to_string(),
+                    vec!["$id_z".into()],
                     469,
                     "SELECT z.col_z1, z.col_z2, b.col_b1, b.col_b2 FROM table_z z
                     INNER JOIN table_b b
                     ON b.col_z1 = z.col_z1 AND b.cond3 = ? AND b.cond4 = ?
-                    WHERE z.col_z1 = $id_z".to_string()
+                    WHERE z.col_z1 = $id_z".to_string(),
+                    0,
                 ),
             ],
             nodes,
         );
     }
+
+    #[test]
+    fn node_found_with_arbitrary_whitespace_and_punctuation() {
+        let query = "SELECT * FROM (combined_result\t(SELECT col_a1 FROM \
+                     table_a)\r\nAS\t$id_a{SELECT a.col_a1 FROM table_a a\r\n\
+                     WHERE a.col_a1 = $id_a})";
+        let inner_query =
+            "SELECT a.col_a1 FROM table_a a WHERE a.col_a1 = $id_a";
+        let expected =
+            CompleteCombinedResultNode::new(15,
+                                            124,
+                                            "SELECT col_a1 FROM table_a".into(),
+                                            vec!["$id_a".into()],
+                                            69,
+                                            inner_query.into(),
+                                            0);
+        assert_eq!(vec![expected], get_combined_result_nodes(query));
+    }
+
+    #[test]
+    fn braces_and_variables_within_literals_and_comments_are_skipped() {
+        let query = "combined_result (SELECT id FROM a WHERE x <> ')') AS $id {
+                         -- ignore $other }
+                         SELECT a.id, JSON_EXTRACT(a.doc, '$.items[0]') FROM a
+                         WHERE a.id = $id AND a.note <> '}' /* } */
+                     }";
+        let inner_query =
+            "SELECT a.id, JSON_EXTRACT(a.doc, '$.items[0]') FROM a
+                           WHERE a.id = $id AND a.note <> '}' /* } */";
+        let expected =
+            CompleteCombinedResultNode::new(0,
+                                            271,
+                                            "SELECT id FROM a WHERE x <> ')'"
+                                                .into(),
+                                            vec!["$id".into()],
+                                            57,
+                                            format!("-- ignore $other }}\n\
+                                                     {inner_query}"),
+                                            0);
+        assert_eq!(vec![expected], get_combined_result_nodes(query));
+    }
+
+    #[test]
+    fn iteration_query_with_nested_parentheses() {
+        let query = "combined_result (SELECT id FROM a WHERE x IN \
+                     (SELECT COUNT(*) FROM c WHERE d = ')')) AS $id {
+                         SELECT a.id FROM a WHERE a.id = $id
+                     }";
+        let expected =
+            CompleteCombinedResultNode::new(0,
+                                            176,
+                                            "SELECT id FROM a WHERE x IN \
+                                             (SELECT COUNT(*) FROM c WHERE \
+                                             d = ')')"
+                                                      .into(),
+                                            vec!["$id".into()],
+                                            92,
+                                            "SELECT a.id FROM a WHERE a.id = \
+                                             $id".into(),
+                                            0);
+        assert_eq!(vec![expected], get_combined_result_nodes(query));
+    }
+
+    #[test]
+    fn composite_iteration_variables_found() {
+        let query = "combined_result (SELECT tenant_id, id FROM a) \
+                     AS ($tenant, $id) { SELECT a.x FROM a \
+                     WHERE a.tenant_id = $tenant AND a.id = $id }";
+        let expected =
+            CompleteCombinedResultNode::new(0,
+                                            127,
+                                            "SELECT tenant_id, id FROM a".into(),
+                                            vec!["$tenant".into(), "$id".into()],
+                                            64,
+                                            "SELECT a.x FROM a WHERE \
+                                             a.tenant_id = $tenant AND \
+                                             a.id = $id".into(),
+                                            0);
+        assert_eq!(vec![expected], get_combined_result_nodes(query));
+    }
+
+    #[test]
+    fn unbalanced_iteration_query_is_reported() {
+        let owned = String::from("SELECT * FROM (combined_result (SELECT id \
+                                  FROM a WHERE x IN (SELECT y FROM c) AS $id \
+                                  { SELECT a.id FROM a WHERE a.id = $id })");
+        let mut parser = ParserState::initialize(&owned);
+
+        let err = parser.parse().unwrap_err();
+        assert!(matches!(err, QueryCompilerError::UnbalancedParenthese(..)));
+        assert_eq!(Span::new(31, 32), err.span());
+    }
+
+    #[test]
+    fn unclosed_variable_tuple_is_reported() {
+        let owned = String::from("combined_result (SELECT a, b FROM x) \
+                                  AS ($a, $b { SELECT x.a FROM x \
+                                  WHERE x.a = $a AND x.b = $b }");
+        let mut parser = ParserState::initialize(&owned);
+
+        let err = parser.parse().unwrap_err();
+        assert!(matches!(err, QueryCompilerError::UnbalancedParenthese(..)));
+        assert_eq!(Span::new(40, 41), err.span());
+
+        // Parentheses within comments or strings don't open a tuple.
+        for declaration in ["AS $a /* ( */", "AS $a -- (\n", "AS ($a /* ) */)"]
+        {
+            let owned = format!("combined_result (SELECT a FROM x) \
+                                 {declaration} {{ SELECT x.a FROM x \
+                                 WHERE x.a = $a }}");
+            let mut parser = ParserState::initialize(&owned);
+            assert!(parser.parse().is_ok(), "{declaration}");
+        }
+    }
+
+    #[test]
+    fn nested_nodes_found() {
+        let query = "combined_result (SELECT id FROM a) AS $id_a {
+                         SELECT a.id, x.id FROM a INNER JOIN (
+                             combined_result (SELECT id FROM b) AS $id_b {
+                                 SELECT b.id FROM b WHERE b.id = $id_b
+                             }
+                         ) x ON x.id = a.id WHERE a.id = $id_a
+                     }";
+        let nested_query = "combined_result (SELECT id FROM b) AS $id_b {
+                                SELECT b.id FROM b WHERE b.id = $id_b
+                            }";
+        let nested =
+            CompleteCombinedResultNode::new(138,
+                                            284,
+                                            "SELECT id FROM b".into(),
+                                            vec!["$id_b".into()],
+                                            182,
+                                            "SELECT b.id FROM b WHERE b.id = \
+                                             $id_b"
+                                                   .into(),
+                                            1);
+        let enclosing =
+            CompleteCombinedResultNode::new(0,
+                                            370,
+                                            "SELECT id FROM a".into(),
+                                            vec!["$id_a".into()],
+                                            44,
+                                            format!("SELECT a.id, x.id FROM a \
+                                                     INNER JOIN ( \
+                                                     {nested_query} ) x ON \
+                                                     x.id = a.id WHERE a.id = \
+                                                     $id_a"),
+                                            0);
+        assert_eq!(vec![nested, enclosing], get_combined_result_nodes(query));
+    }
+
+    #[test]
+    fn incomplete_node_is_reported() {
+        let owned = String::from("combined_result\n(SELECT col_a1 FROM \
+                                  table_a) AS $id_a { SELECT a.col_a1 FROM \
+                                  table_a a");
+        let mut parser = ParserState::initialize(&owned);
+        parser.parse().unwrap();
+
+        assert!(parser.finalize().is_err());
+    }
+
+    #[test]
+    fn parser_recovers_after_incomplete_node() {
+        let owned =
+            String::from("combined_result (SELECT id FROM a) AS $id_a \
+                                  combined_result (SELECT id FROM b) AS $id_b \
+                                  { SELECT b.id FROM b WHERE b.id = $id_b }");
+        let mut parser = ParserState::initialize(&owned);
+        parser.parse().unwrap();
+
+        let err = parser.finalize().err().unwrap();
+        assert!(matches!(err, QueryCompilerError::DirectiveIncomplete(..)));
+        assert_eq!(Span::new(0, 15), err.span());
+    }
+
+    #[test]
+    fn all_errors_are_reported() {
+        let owned = String::from("combined_result (SELECT id FROM a AS $id_a \
+                                  { SELECT 1 } UNION ALL \
+                                  combined_result (SELECT id FROM b) AS $id_b \
+                                  UNION ALL \
+                                  combined_result (SELECT id FROM c) AS $id_c \
+                                  { SELECT c.id FROM c WHERE c.id = $id_c }");
+        let mut parser = ParserState::initialize(&owned);
+
+        let errors = parser.parse().unwrap_err().into_errors();
+        assert_eq!(2, errors.len());
+        assert!(matches!(errors[0],
+                         QueryCompilerError::UnbalancedParenthese(..)));
+        assert_eq!(Span::new(16, 17), errors[0].span());
+        assert!(matches!(errors[1],
+                         QueryCompilerError::DirectiveIncomplete(..)));
+        assert_eq!(Span::new(66, 81), errors[1].span());
+    }
 }
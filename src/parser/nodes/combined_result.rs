@@ -1,25 +1,51 @@
 use {
     crate::{
-        codegen::mysql84::MySql84NodeCompiler,
+        codegen::{
+            common::splice_generated_code,
+            Dialect,
+            Feature,
+            NodeCompiler,
+        },
         error::QueryCompilerError,
-        lex::KEYWORD_COMBINED_RESULT,
+        lex::{
+            PlaceholderStyle,
+            QuotingStyle,
+            Span,
+            TokenKind,
+            KEYWORD_COMBINED_RESULT,
+        },
+        parameters::to_numeric_placeholders,
         parser::nodes::Node,
+        scanner::Scanner,
     },
     sqlparser::{
         ast::{helpers::attached_token::AttachedToken, *},
-        dialect::GenericDialect,
+        dialect::Dialect as ParserDialect,
+        keywords::Keyword,
         parser::Parser,
+        tokenizer::Token,
     },
 };
 
 #[derive(Clone, Debug)]
 pub struct CombinedResultNode {
+    pub depth: usize,
     pub begin_position: usize,
     pub end_position: Option<usize>,
     pub iteration_query: Option<String>,
-    pub iteration_item_variable: Option<String>,
+    pub iteration_query_end: Option<usize>,
+    /// The iteration variables following `AS`, i.e. either a single one
+    /// (`AS $id`) or a tuple (`AS ($tenant, $id)`).
+    pub iteration_item_variables: Vec<String>,
+    /// The position of the opening parenthese of a tuple of iteration
+    /// variables which hasn't been closed yet.
+    pub unclosed_tuple_begin: Option<usize>,
     pub inner_query_begin: Option<usize>,
     pub inner_query: Option<String>,
+    /// Whether an error has already been reported for this node. Failed
+    /// nodes are still tracked until their closing brace, but they are
+    /// neither reported as incomplete nor compiled.
+    pub failed: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -27,28 +53,123 @@ pub struct CompleteCombinedResultNode {
     begin_position: usize,
     end_position: usize,
     iteration_query: String,
-    iteration_item_variable: String,
+    /// The span of the iteration query within the statement, if known.
+    iteration_query_span: Span,
+    iteration_item_variables: Vec<String>,
     inner_query_begin: usize,
     inner_query: String,
+    depth: usize,
 }
 
 impl CompleteCombinedResultNode {
     pub fn new(begin_position: usize,
                end_position: usize,
                iteration_query: String,
-               iteration_item_variable: String,
+               iteration_item_variables: Vec<String>,
                inner_query_begin: usize,
-               inner_query: String)
+               inner_query: String,
+               depth: usize)
                -> Self {
         Self { begin_position,
                end_position,
                iteration_query,
-               iteration_item_variable,
+               iteration_query_span: Span::default(),
+               iteration_item_variables,
                inner_query_begin,
-               inner_query }
+               inner_query,
+               depth }
+    }
+
+    pub fn with_iteration_query_span(mut self, span: Span) -> Self {
+        self.iteration_query_span = span;
+        self
+    }
+
+    /// Name of the relation collecting all results, i.e. the recursive CTE
+    /// for MySQL or the `LATERAL` subquery for PostgreSQL.
+    ///
+    /// Nested directives are compiled into nested relations, so each
+    /// nesting level gets its own name.
+    fn all_entries_name(&self) -> String {
+        self.get_cte_name("all_entries")
+    }
+
+    /// Name of the relation containing the result of the iteration query.
+    fn loop_values_name(&self) -> String {
+        self.get_cte_name("loop_values")
+    }
+
+    /// Names of the columns of the relation containing the result of the
+    /// iteration query, one per iteration variable.
+    fn loop_value_columns(&self) -> Vec<String> {
+        match self.iteration_item_variables.len()
+        {
+            1 => vec![LOOP_VALUE_COLUMN.into()],
+            count => (1 ..= count).map(|index| {
+                                      format!("{LOOP_VALUE_COLUMN}_{index}")
+                                  })
+                                  .collect(),
+        }
+    }
+
+    /// Fails if the iteration query explicitly projects a number of columns
+    /// other than the number of iteration variables, which would be
+    /// rejected by the database (or bind the wrong values) otherwise.
+    fn check_iteration_projection(&self,
+                                  iteration_query: &Query)
+                                  -> Result<(), QueryCompilerError> {
+        let variables = &self.iteration_item_variables;
+        match count_projected_columns(&iteration_query.body)
+        {
+            Some(columns) if columns != variables.len() =>
+            {
+                let declaration = match variables.as_slice()
+                {
+                    [variable] => variable.clone(),
+                    tuple => format!("({})", tuple.join(", ")),
+                };
+                let msg = format!("iteration query projects {columns} \
+                                   column(s), but {} are bound to \
+                                   `{declaration}`",
+                                  variables.len());
+                Err(QueryCompilerError::InnerQueryInvalid(
+                    msg,
+                    self.iteration_query_span))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn get_cte_name(&self, base: &str) -> String {
+        match self.depth
+        {
+            0 => base.into(),
+            depth => format!("{base}_{depth}"),
+        }
+    }
+
+    /// Fails unless the `dialect` supports `feature`, which this directive
+    /// is compiled to.
+    fn require(&self,
+               dialect: &dyn Dialect,
+               feature: Feature)
+               -> Result<(), QueryCompilerError> {
+        dialect.require(feature).map_err(|requirement| {
+                                    QueryCompilerError::UnsupportedByDialect(
+                KEYWORD_COMBINED_RESULT,
+                dialect.to_string(),
+                requirement,
+                get_keyword_span(self.begin_position))
+                                })
     }
 }
 
+/// The span of the `combined_result` keyword of a directive.
+fn get_keyword_span(begin_position: usize) -> Span {
+    Span::new(begin_position,
+              begin_position + KEYWORD_COMBINED_RESULT.len())
+}
+
 impl Node for CompleteCombinedResultNode {
     fn get_begin_position(&self) -> usize {
         self.begin_position
@@ -61,6 +182,19 @@ impl Node for CompleteCombinedResultNode {
     fn get_end_position(&self) -> usize {
         self.end_position
     }
+
+    fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    fn embed_nested_code(&mut self,
+                         statement: &str,
+                         nested_code: Vec<(Span, String)>) {
+        let scope = Span::new(self.inner_query_begin + 1, self.end_position);
+        self.inner_query =
+            splice_generated_code(statement, scope, nested_code).trim()
+                                                                .into();
+    }
 }
 
 fn normalize_query(query: &str) -> String {
@@ -77,21 +211,26 @@ impl PartialEq for CompleteCombinedResultNode {
         self.begin_position == other.begin_position
         && self.end_position == other.end_position
         && self.iteration_query == other.iteration_query
-        && self.iteration_item_variable == other.iteration_item_variable
+        && self.iteration_item_variables == other.iteration_item_variables
         && self.inner_query_begin == other.inner_query_begin
+        && self.depth == other.depth
         && normalize_query(&self.inner_query)
            == normalize_query(&other.inner_query)
     }
 }
 
 impl CombinedResultNode {
-    pub fn new(begin_position: usize) -> Self {
-        Self { begin_position,
+    pub fn new(begin_position: usize, depth: usize) -> Self {
+        Self { depth,
+               begin_position,
                end_position: None,
                iteration_query: None,
-               iteration_item_variable: None,
+               iteration_query_end: None,
+               iteration_item_variables: vec![],
+               unclosed_tuple_begin: None,
                inner_query_begin: None,
-               inner_query: None }
+               inner_query: None,
+               failed: false }
     }
 }
 
@@ -101,77 +240,158 @@ impl TryFrom<CombinedResultNode> for CompleteCombinedResultNode {
     fn try_from(value: CombinedResultNode) -> Result<Self, Self::Error> {
         if value.iteration_query.is_none()
            || value.end_position.is_none()
-           || value.iteration_item_variable.is_none()
+           || value.iteration_item_variables.is_empty()
            || value.inner_query_begin.is_none()
            || value.inner_query.is_none()
         {
             let err = QueryCompilerError::DirectiveIncomplete(
                 KEYWORD_COMBINED_RESULT,
-                value.begin_position);
+                get_keyword_span(value.begin_position));
             return Err(err);
         }
 
+        let iteration_query = value.iteration_query.unwrap();
+        let iteration_query_span =
+            value.iteration_query_end
+                 .map_or(Span::default(), |end| {
+                     Span::new(end - iteration_query.len(), end)
+                 });
         let node =
             CompleteCombinedResultNode::new(value.begin_position,
                                             value.end_position.unwrap(),
-                                            value.iteration_query.unwrap(),
-                                            value.iteration_item_variable
-                                                 .unwrap(),
+                                            iteration_query,
+                                            value.iteration_item_variables,
                                             value.inner_query_begin.unwrap(),
-                                            value.inner_query.unwrap());
+                                            value.inner_query.unwrap(),
+                                            value.depth)
+                .with_iteration_query_span(iteration_query_span);
         Ok(node)
     }
 }
 
 impl From<sqlparser::parser::ParserError> for QueryCompilerError {
     fn from(value: sqlparser::parser::ParserError) -> Self {
-        Self::InnerQueryInvalid(value.to_string())
+        Self::inner_query_invalid(value.to_string())
     }
 }
 
-impl MySql84NodeCompiler for CompleteCombinedResultNode {
-    fn generate_code(&self) -> Result<String, QueryCompilerError> {
+impl NodeCompiler for CompleteCombinedResultNode {
+    /// Compiles the directive to `LATERAL` joins if the dialect prefers (and
+    /// supports) them, or to a recursive CTE otherwise.
+    fn generate_code(&self,
+                     dialect: &dyn Dialect)
+                     -> Result<String, QueryCompilerError> {
+        if dialect.prefers_lateral_joins()
+           && dialect.supports(Feature::LateralJoin)
+        {
+            return self.compile_lateral_join(dialect);
+        }
+
+        self.require(dialect, Feature::RecursiveCte)?;
+        let parser_dialect = dialect.parser_dialect();
+        let parser_dialect = parser_dialect.as_ref();
         let original_select =
-            prepare_parser_with_query(&self.inner_query)?.parse_select()?;
+            parse_inner_select(&self.inner_query, parser_dialect)?;
 
-        let final_select = compile_final_select(&original_select, self)?;
+        let mut final_select = compile_final_select(&original_select,
+                                                    self,
+                                                    parser_dialect,
+                                                    dialect.quoting_style())?;
+        if !dialect.supports(Feature::NestedCte)
+        {
+            hoist_nested_ctes(&mut final_select);
+        }
 
         Ok(final_select.to_string())
     }
 }
 
+/// Moves the CTEs declared within the definitions of the CTEs of `query` to
+/// the `WITH` clause of `query` itself, in front of the CTEs using them.
+fn hoist_nested_ctes(query: &mut Query) {
+    if let Some(with) = query.with.as_mut()
+    {
+        let nested_ctes = with.cte_tables
+                              .iter_mut()
+                              .filter_map(|cte| cte.query.with.take())
+                              .flat_map(|nested| nested.cte_tables)
+                              .collect::<Vec<_>>();
+        with.cte_tables.splice(0 .. 0, nested_ctes);
+    }
+}
+
+/// Compiles the directive to a recursive CTE looking up one iteration value
+/// per iteration, in ascending order:
+///
+/// ```sql
+/// WITH RECURSIVE all_entries (loop_value, loop_match, ...) AS (
+///     WITH loop_values (loop_value) AS (
+///         SELECT DISTINCT * FROM (iteration query) AS loop_values
+///     )
+///     SELECT loop_key.loop_value, loop_matches.loop_match, ...
+///     FROM loop_values AS loop_key
+///     CROSS JOIN (SELECT 0 AS loop_match UNION ALL SELECT 1) AS loop_matches
+///     LEFT JOIN (tables of inner query)
+///     ON loop_matches.loop_match = 1 AND a.id = loop_key.loop_value AND ...
+///     WHERE loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values)
+///     AND (loop_matches.loop_match = 0 OR a.id = loop_key.loop_value)
+///     UNION ALL
+///     SELECT loop_key.loop_value, loop_matches.loop_match, ...
+///     FROM all_entries
+///     INNER JOIN loop_values AS loop_key
+///     ON loop_key.loop_value = (SELECT MIN(loop_value) FROM loop_values
+///                               WHERE loop_value > all_entries.loop_value)
+///     CROSS JOIN ... LEFT JOIN ... (like above)
+///     WHERE all_entries.loop_match = 0 AND (...)
+/// )
+/// SELECT ... FROM all_entries WHERE loop_match = 1
+/// ```
+///
+/// Each iteration yields the rows of the inner query for its value
+/// (`loop_match = 1`) and a single row the next iteration continues from
+/// (`loop_match = 0`). So the iteration neither stops at values without any
+/// rows, nor repeats values following those with several rows.
 fn compile_final_select(original_select: &Select,
-                        node: &CompleteCombinedResultNode)
+                        node: &CompleteCombinedResultNode,
+                        dialect: &dyn ParserDialect,
+                        quoting_style: QuotingStyle)
                         -> Result<Box<Query>, QueryCompilerError> {
+    if original_select.from.len() != 1
+    {
+        let msg = "inner query may only have one table following
+                   FROM directive";
+        return Err(QueryCompilerError::inner_query_invalid(msg));
+    }
+    let conditions =
+        find_inner_query_conditions(original_select, node, quoting_style)?;
     let original_select_column_idents =
         derive_original_select_columns(original_select);
 
     let cte_columns =
-        construct_recursive_cte_columns(&original_select_column_idents);
-    let cte_statement =
-        construct_recursive_cte_statement(original_select, cte_columns, node)?;
+        construct_recursive_cte_columns(&original_select_column_idents, node);
+    let cte_statement = construct_recursive_cte_statement(original_select,
+                                                          cte_columns,
+                                                          node,
+                                                          &conditions,
+                                                          dialect)?;
 
-    let select = compile_recursive_cte(original_select, cte_statement)?;
+    let select = compile_recursive_cte(original_select_column_idents,
+                                       cte_statement,
+                                       node,
+                                       dialect)?;
     Ok(select)
 }
 
-fn compile_recursive_cte(original_select: &Select,
-                         cte_statement: With)
+fn compile_recursive_cte(original_select_column_idents: Vec<Ident>,
+                         cte_statement: With,
+                         node: &CompleteCombinedResultNode,
+                         dialect: &dyn ParserDialect)
                          -> Result<Box<Query>, QueryCompilerError> {
-    let original_select_column_idents =
-        derive_original_select_columns(original_select);
-
-    let where_fragments =
-        derive_joined_table_column_names(original_select)
-        .unwrap_or_default()
-        .iter()
-        .map(|identifier| format!("{identifier} IS NOT NULL"))
-        .collect::<Vec<String>>()
-        .join(" AND ");
-
-    let mut select = prepare_parser_with_query(format!(
-        "SELECT * FROM all_entries WHERE {where_fragments}"
-    ).as_str())?.parse_query()?;
+    let all_entries = node.all_entries_name();
+    let mut select =
+        prepare_parser_with_query(&format!("SELECT * FROM {all_entries} \
+                                            WHERE {LOOP_MATCH_COLUMN} = 1"),
+                                  dialect)?.parse_query()?;
     select.with = Some(cte_statement);
     let mut select_body = select.body
                                 .as_select()
@@ -185,46 +405,28 @@ fn compile_recursive_cte(original_select: &Select,
     Ok(select)
 }
 
-fn derive_joined_table_column_names(original_select: &Select)
-                                    -> Option<Vec<String>> {
-    let original_select_column_ident_pairs =
-        derive_fully_qualified_original_select_columns(original_select);
-
-    if let TableFactor::Table { name,
-                                alias,
-                                .. } = &original_select.from[0].relation
-    {
-        let target_to_elide = if alias.is_some()
-        {
-            alias.clone().unwrap().to_string()
-        }
-        else
-        {
-            name.to_string()
-        };
-
-        return Some(original_select_column_ident_pairs.iter()
-                                                      .filter(|&pair| {
-                                                          pair.0.value
-                                                          != target_to_elide
-                                                      })
-                                                      .map(|pair| {
-                                                          pair.1.value.clone()
-                                                      })
-                                                      .collect());
-    }
-
-    None
-}
-
-fn construct_cte_with_iteration(node: &CompleteCombinedResultNode)
+/// Constructs `loop_values`, the deduplicated result of the iteration query.
+fn construct_cte_with_iteration(node: &CompleteCombinedResultNode,
+                                dialect: &dyn ParserDialect)
                                 -> Result<With, QueryCompilerError> {
+    let loop_values = node.loop_values_name();
+    let columns =
+        node.loop_value_columns()
+            .into_iter()
+            .map(|column| TableAliasColumnDef { name: Ident::new(column),
+                                                data_type: None })
+            .collect();
+    // The iteration query is embedded as is, but it is validated on its own,
+    // as trailing syntax would be dropped when parsing the embedding query.
+    let iteration_query = parse_complete_query(&node.iteration_query, dialect)?;
+    node.check_iteration_projection(&iteration_query)?;
+    let query = format!("SELECT DISTINCT * FROM ({}) AS {loop_values}",
+                        node.iteration_query);
     let cte =
-        Cte { alias: TableAlias { name: "loop_values".into(),
-                                  columns: vec![] },
+        Cte { alias: TableAlias { name: Ident::new(loop_values),
+                                  columns },
               query:
-                  prepare_parser_with_query(&node.iteration_query)?
-                  .parse_query()?,
+                  prepare_parser_with_query(&query, dialect)?.parse_query()?,
               from: None,
               materialized: None,
               closing_paren_token: AttachedToken::empty() };
@@ -236,14 +438,19 @@ fn construct_cte_with_iteration(node: &CompleteCombinedResultNode)
 
 fn construct_recursive_cte_statement(original_select: &Select,
                                      cte_columns: Vec<TableAliasColumnDef>,
-                                     node: &CompleteCombinedResultNode)
+                                     node: &CompleteCombinedResultNode,
+                                     conditions: &InnerQueryConditions,
+                                     dialect: &dyn ParserDialect)
                                      -> Result<With, QueryCompilerError> {
     let cte =
-        Cte { alias: TableAlias { name: "all_entries".into(),
+        Cte { alias: TableAlias { name:
+                                      Ident::new(node.all_entries_name()),
                                   columns: cte_columns },
               query:
                   Box::new(construct_recursive_cte_query(original_select,
-                                                         node)?),
+                                                         node,
+                                                         conditions,
+                                                         dialect)?),
               from: None,
               materialized: None,
               closing_paren_token: AttachedToken::empty() };
@@ -254,18 +461,26 @@ fn construct_recursive_cte_statement(original_select: &Select,
 }
 
 fn construct_recursive_cte_query(original_select: &Select,
-                                 node: &CompleteCombinedResultNode)
+                                 node: &CompleteCombinedResultNode,
+                                 conditions: &InnerQueryConditions,
+                                 dialect: &dyn ParserDialect)
                                  -> Result<Query, QueryCompilerError> {
     Ok(Query {
         body: Box::new(
             SetExpr::SetOperation {
                 op: SetOperator::Union,
                 set_quantifier: SetQuantifier::All,
-                left: compile_cte_anchor(original_select, node)?,
-                right: compile_cte_loop(original_select, node)?,
+                left: compile_cte_anchor(original_select,
+                                         node,
+                                         conditions,
+                                         dialect)?,
+                right: compile_cte_loop(original_select,
+                                        node,
+                                        conditions,
+                                        dialect)?,
             }
         ),
-        with: Some(construct_cte_with_iteration(node)?),
+        with: Some(construct_cte_with_iteration(node, dialect)?),
         order_by: None,
         limit_clause: None,
         fetch: None,
@@ -277,15 +492,17 @@ fn construct_recursive_cte_query(original_select: &Select,
     })
 }
 
-fn construct_recursive_cte_columns(original_select_column_idents: &[Ident])
+fn construct_recursive_cte_columns(original_select_column_idents: &[Ident],
+                                   node: &CompleteCombinedResultNode)
                                    -> Vec<TableAliasColumnDef> {
-    let mut cte_idents: Vec<Ident> = original_select_column_idents.into();
-    cte_idents.insert(0, Ident::new("n"));
-
-    cte_idents.into_iter()
-              .map(|ident| TableAliasColumnDef { name: ident,
-                                                 data_type: None })
-              .collect()
+    node.loop_value_columns()
+        .into_iter()
+        .chain([LOOP_MATCH_COLUMN.into()])
+        .map(Ident::new)
+        .chain(original_select_column_idents.iter().cloned())
+        .map(|ident| TableAliasColumnDef { name: ident,
+                                           data_type: None })
+        .collect()
 }
 
 fn derive_original_select_columns(original_select: &Select) -> Vec<Ident> {
@@ -295,16 +512,6 @@ fn derive_original_select_columns(original_select: &Select) -> Vec<Ident> {
                    .collect()
 }
 
-fn derive_fully_qualified_original_select_columns(original_select: &Select)
-                                                  -> Vec<(Ident, Ident)> {
-    original_select.projection
-                   .iter()
-                   .filter_map(
-                        convert_select_item_to_full_qualified_idents_option
-                   )
-                   .collect()
-}
-
 fn convert_select_item_to_ident_option(item: &SelectItem) -> Option<Ident> {
     match item
     {
@@ -317,76 +524,208 @@ fn convert_select_item_to_ident_option(item: &SelectItem) -> Option<Ident> {
     }
 }
 
-fn convert_select_item_to_full_qualified_idents_option(
-    item: &SelectItem)
-    -> Option<(Ident, Ident)> {
-    match item
+/// Compiles the first iteration, looking up the smallest iteration value.
+/// Tuples of iteration values are ordered lexicographically.
+fn compile_cte_anchor(original_select: &Select,
+                      node: &CompleteCombinedResultNode,
+                      conditions: &InnerQueryConditions,
+                      dialect: &dyn ParserDialect)
+                      -> Result<Box<SetExpr>, QueryCompilerError> {
+    let source = format!("{} AS {LOOP_KEY}", node.loop_values_name());
+    let condition = format!("{} = {}",
+                            to_row_value(qualify_loop_value_columns(node,
+                                                                    LOOP_KEY)),
+                            construct_smallest_loop_value(node, None));
+    compile_cte_iteration(original_select,
+                          &source,
+                          &condition,
+                          node,
+                          conditions,
+                          dialect)
+}
+
+/// Compiles all further iterations, each looking up the smallest iteration
+/// value greater than the one of the previous iteration. The iteration ends
+/// as soon as there is no such value.
+fn compile_cte_loop(original_select: &Select,
+                    node: &CompleteCombinedResultNode,
+                    conditions: &InnerQueryConditions,
+                    dialect: &dyn ParserDialect)
+                    -> Result<Box<SetExpr>, QueryCompilerError> {
+    let all_entries = node.all_entries_name();
+    let following = format!("{} > {}",
+                to_row_value(node.loop_value_columns()),
+                to_row_value(qualify_loop_value_columns(node, &all_entries)));
+    let source = format!("{all_entries} \
+                          INNER JOIN {} AS {LOOP_KEY} ON {} = {}",
+                         node.loop_values_name(),
+                         to_row_value(qualify_loop_value_columns(node,
+                                                                 LOOP_KEY)),
+                         construct_smallest_loop_value(node, Some(&following)));
+    let condition = format!("{all_entries}.{LOOP_MATCH_COLUMN} = 0");
+    compile_cte_iteration(original_select,
+                          &source,
+                          &condition,
+                          node,
+                          conditions,
+                          dialect)
+}
+
+/// The subquery selecting the smallest iteration value of `loop_values`
+/// fulfilling `condition` (if any). Tuples of iteration values are ordered
+/// lexicographically.
+///
+/// Iteration values containing `NULL` are skipped, as comparisons to them
+/// are never true. `MIN` skips them anyway, but a tuple containing `NULL`
+/// would be the smallest one otherwise, so that the iteration never starts.
+fn construct_smallest_loop_value(node: &CompleteCombinedResultNode,
+                                 condition: Option<&str>)
+                                 -> String {
+    let loop_values = node.loop_values_name();
+    match node.loop_value_columns().as_slice()
     {
-        SelectItem::UnnamedExpr(Expr::CompoundIdentifier(idents))
-            if idents.len() == 2 =>
+        [column] =>
         {
-            Some((idents[0].clone(), idents[1].clone()))
+            let condition =
+                condition.map(|condition| format!(" WHERE {condition}"))
+                         .unwrap_or_default();
+            format!("(SELECT MIN({column}) FROM {loop_values}{condition})")
+        },
+        columns =>
+        {
+            let condition =
+                columns.iter()
+                       .map(|column| format!("{column} IS NOT NULL"))
+                       .chain(condition.map(String::from))
+                       .collect::<Vec<_>>()
+                       .join(" AND ");
+            let columns = columns.join(", ");
+            format!("(SELECT {columns} FROM {loop_values} WHERE {condition} \
+                     ORDER BY {columns} LIMIT 1)")
         },
-        _ => None,
     }
 }
 
-fn compile_cte_anchor(original_select: &Select,
-                      node: &CompleteCombinedResultNode)
-                      -> Result<Box<SetExpr>, QueryCompilerError> {
-    let mut cte_anchor = original_select.clone();
-    if cte_anchor.from.len() != 1
+/// The columns of `loop_values` qualified by `qualifier`, e.g.
+/// `loop_key.loop_value`.
+fn qualify_loop_value_columns(node: &CompleteCombinedResultNode,
+                              qualifier: &str)
+                              -> Vec<String> {
+    node.loop_value_columns()
+        .into_iter()
+        .map(|column| format!("{qualifier}.{column}"))
+        .collect()
+}
+
+/// Combines `values` to a row value like `(a, b)`, unless there is just a
+/// single value.
+fn to_row_value(values: Vec<String>) -> String {
+    match values.as_slice()
     {
-        let msg = "inner query may only have one table following
-                   FROM directive";
-        return Err(QueryCompilerError::InnerQueryInvalid(msg.into()));
+        [value] => value.clone(),
+        _ => format!("({})", values.join(", ")),
     }
-
-    insert_anchor_iteration_index(&mut cte_anchor)?;
-    transform_all_joins_to_left_joins(&mut cte_anchor);
-    apply_anchor_iteration_variable(&mut cte_anchor, node)?;
-
-    Ok(Box::new(SetExpr::Select(Box::new(cte_anchor))))
 }
 
-fn apply_anchor_iteration_variable(cte_anchor: &mut Select,
-                                   node: &CompleteCombinedResultNode)
-                                   -> Result<(), QueryCompilerError> {
-    if let Some(selection) = &cte_anchor.selection
+/// Compiles an iteration of the recursive CTE, which joins the tables of the
+/// inner query to `source` (providing the iteration value as `loop_key`),
+/// restricted by `condition`.
+fn compile_cte_iteration(original_select: &Select,
+                         source: &str,
+                         condition: &str,
+                         node: &CompleteCombinedResultNode,
+                         conditions: &InnerQueryConditions,
+                         dialect: &dyn ParserDialect)
+                         -> Result<Box<SetExpr>, QueryCompilerError> {
+    let loop_match = format!("{LOOP_MATCHES}.{LOOP_MATCH_COLUMN}");
+    let matches = conditions.comparisons
+                            .iter()
+                            .zip(node.loop_value_columns())
+                            .map(|(comparison, column)| {
+                                format!("{} = {LOOP_KEY}.{column}",
+                                        comparison.column)
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" AND ");
+    let loop_values = qualify_loop_value_columns(node, LOOP_KEY).join(", ");
+    let iteration =
+        format!("SELECT {loop_values}, {loop_match} \
+                 FROM {source} \
+                 CROSS JOIN (SELECT 0 AS {LOOP_MATCH_COLUMN} UNION ALL \
+                 SELECT 1) AS {LOOP_MATCHES} \
+                 WHERE {condition} \
+                 AND ({loop_match} = 0 OR {matches})");
+    let mut iteration =
+        prepare_parser_with_query(&iteration, dialect)?.parse_select()?;
+
+    let mut join_condition = format!("{loop_match} = 1 AND {matches}");
+    if let Some(remainder) = &conditions.remainder
     {
-        let transformed_selection_fragment =
-            selection.to_string()
-                     .replace(&node.iteration_item_variable,
-                              "(SELECT * FROM loop_values LIMIT 1)");
-        let transformed_selection =
-            prepare_parser_with_query(&transformed_selection_fragment)?
-            .parse_expr()?;
-        cte_anchor.selection = Some(transformed_selection);
+        join_condition.push_str(&format!(" AND ({remainder})"));
     }
-    Ok(())
+    let join_condition =
+        prepare_parser_with_query(&join_condition, dialect)?.parse_expr()?;
+    let relation =
+        construct_inner_query_relation(original_select, node, conditions);
+    let join_operator = JoinOperator::Left(JoinConstraint::On(join_condition));
+    let join = Join { relation,
+                      global: false,
+                      join_operator };
+    iteration.from[0].joins.push(join);
+
+    let mut select = original_select.clone();
+    select.projection = iteration.projection;
+    select.projection
+          .extend(original_select.projection.iter().cloned());
+    select.from = iteration.from;
+    select.selection = iteration.selection;
+    Ok(Box::new(SetExpr::Select(Box::new(select))))
 }
 
-fn transform_all_joins_to_left_joins(select: &mut Select) {
-    select
-        .from[0]
-        .joins
-        .iter_mut()
-        .for_each(|join| {
-        if let Some(constraint) = derive_join_constraint(join) {
-            join.join_operator = JoinOperator::Left(constraint.clone());
+/// The tables of the inner query, joined as within the inner query, except
+/// that the comparisons with the iteration variables are removed from the
+/// `ON` conditions containing them (see `compile_cte_iteration`).
+fn construct_inner_query_relation(original_select: &Select,
+                                  node: &CompleteCombinedResultNode,
+                                  conditions: &InnerQueryConditions)
+                                  -> TableFactor {
+    let mut tables = original_select.from[0].clone();
+    for comparison in &conditions.comparisons
+    {
+        if let ComparisonLocation::Join(index) = comparison.location
+        {
+            if let Some(condition) =
+                get_inner_join_condition_mut(&mut tables.joins[index])
+            {
+                *condition =
+                    remove_comparisons(condition,
+                                       &node.iteration_item_variables)
+                    .unwrap_or(Expr::Value(Value::Boolean(true).into()));
+            }
         }
-        });
+    }
+
+    match tables.joins.is_empty()
+    {
+        true => tables.relation,
+        false => TableFactor::NestedJoin { table_with_joins:
+                                               Box::new(tables),
+                                           alias: None },
+    }
 }
 
-fn insert_anchor_iteration_index(cte_anchor: &mut Select)
-                                 -> Result<(), QueryCompilerError> {
-    cte_anchor.projection.insert(
-        0,
-        SelectItem::UnnamedExpr(
-            prepare_parser_with_query("0")?.parse_expr()?,
-        )
-    );
-    Ok(())
+/// Returns the `ON` condition of `join`, if it is an inner join.
+fn get_inner_join_condition_mut(join: &mut Join) -> Option<&mut Expr> {
+    match &mut join.join_operator
+    {
+        JoinOperator::Join(JoinConstraint::On(condition))
+        | JoinOperator::Inner(JoinConstraint::On(condition))
+        | JoinOperator::StraightJoin(JoinConstraint::On(condition)) =>
+        {
+            Some(condition)
+        },
+        _ => None,
+    }
 }
 
 fn derive_join_constraint(join: &Join) -> Option<&JoinConstraint> {
@@ -414,219 +753,629 @@ fn derive_join_constraint(join: &Join) -> Option<&JoinConstraint> {
     }
 }
 
-fn compile_cte_loop(original_select: &Select,
-                    node: &CompleteCombinedResultNode)
-                    -> Result<Box<SetExpr>, QueryCompilerError> {
-    let mut cte_loop = original_select.clone();
-    if cte_loop.from.len() != 1
-    {
-        let msg = "inner query may only have one table following
-                   FROM directive";
-        return Err(QueryCompilerError::InnerQueryInvalid(msg.into()));
-    }
-    insert_loop_iteration_index(&mut cte_loop)?;
-    transform_loop_table_name_to_cte_alias(&mut cte_loop);
-    transform_all_joins_to_left_joins(&mut cte_loop);
-    add_loop_join(&mut cte_loop, node)?;
-    finalize_selection(&mut cte_loop)?;
+/// The column of the iteration query's result within `loop_values`, whatever
+/// the iteration query names it. Within `LATERAL` subqueries, it replaces
+/// the iteration variable, while recursive CTEs carry the iteration value of
+/// each row within it.
+const LOOP_VALUE_COLUMN: &str = "loop_value";
 
-    Ok(Box::new(SetExpr::Select(Box::new(cte_loop))))
-}
+/// The alias of `loop_values` providing the iteration value of an iteration
+/// of the recursive CTE.
+const LOOP_KEY: &str = "loop_key";
 
-fn finalize_selection(cte_loop: &mut Select) -> Result<(), QueryCompilerError> {
-    let lhs = prepare_parser_with_query("n + 1")?.parse_expr()?;
+/// The derived table doubling each iteration of the recursive CTE, see
+/// `LOOP_MATCH_COLUMN`.
+const LOOP_MATCHES: &str = "loop_matches";
 
-    let subquery =
-        prepare_parser_with_query("SELECT COUNT(*) FROM loop_values")?
-        .parse_query()?;
+/// The column distinguishing the rows of the inner query (1) from the single
+/// row per iteration the next iteration continues from (0).
+const LOOP_MATCH_COLUMN: &str = "loop_match";
 
-    cte_loop.selection =
-        Some(Expr::BinaryOp { left: Box::new(lhs),
-                              op: BinaryOperator::Lt,
-                              right: Box::new(Expr::Subquery(subquery)) });
+/// The conditions of the inner query, see `find_inner_query_conditions`.
+#[derive(Debug, PartialEq)]
+struct InnerQueryConditions {
+    /// The comparisons with the iteration variables, in order of their
+    /// declaration.
+    comparisons: Vec<IterationVariableComparison>,
+    /// All further conditions of the `WHERE` clause, if any.
+    remainder: Option<Expr>,
+}
 
-    Ok(())
+/// The comparison of a column with an iteration variable within the inner
+/// query, e.g. `a.id = $id`.
+#[derive(Debug, PartialEq)]
+struct IterationVariableComparison {
+    /// The column compared with the iteration variable, e.g. `a.id`.
+    column: Expr,
+    /// Where the comparison is located.
+    location: ComparisonLocation,
+}
+
+/// The location of an `IterationVariableComparison` within the inner query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ComparisonLocation {
+    /// The `WHERE` clause.
+    Where,
+    /// The `ON` condition of the join at the given index, which is an inner
+    /// join.
+    Join(usize),
+}
+
+/// Finds the comparisons of columns with the iteration variables of `node`
+/// within `select`, its inner query, and the remaining conditions of its
+/// `WHERE` clause.
+fn find_inner_query_conditions(
+    select: &Select,
+    node: &CompleteCombinedResultNode,
+    quoting_style: QuotingStyle)
+    -> Result<InnerQueryConditions, QueryCompilerError> {
+    let variables = &node.iteration_item_variables;
+    let comparisons = variables.iter()
+                               .map(|variable| {
+                                   find_iteration_variable_comparison(select,
+                                                        variable,
+                                                        &node.inner_query,
+                                                        quoting_style)
+                               })
+                               .collect::<Result<Vec<_>, _>>()?;
+    let remainder =
+        select.selection
+              .as_ref()
+              .and_then(|selection| remove_comparisons(selection, variables));
+    Ok(InnerQueryConditions { comparisons,
+                              remainder })
 }
 
-fn add_loop_join(cte_loop: &mut Select,
-                 node: &CompleteCombinedResultNode)
-                 -> Result<(), QueryCompilerError> {
-    let (loop_target_table_or_alias, loop_target_column) =
-        extract_table_and_column_for_iteration_variable(cte_loop,
-                                          &node.iteration_item_variable)?;
-    let loop_target_column_name = loop_target_column.clone().value;
+/// Finds the comparison of a column with the iteration `variable` within
+/// `select`, the parsed `inner_query`.
+///
+/// The comparison may be located anywhere within a chain of `AND`
+/// conditions (parenthesized or not) of the `WHERE` clause or of the `ON`
+/// condition of an inner join, with the iteration variable on either side of
+/// `=`. As each iteration looks up a single value, the iteration variable
+/// must not be referenced anywhere else within the inner query.
+fn find_iteration_variable_comparison(
+    select: &Select,
+    variable: &str,
+    inner_query: &str,
+    quoting_style: QuotingStyle)
+    -> Result<IterationVariableComparison, QueryCompilerError> {
+    let invalid =
+        |message: String| Err(QueryCompilerError::inner_query_invalid(message));
+    let references = count_iteration_variable_references(inner_query,
+                                                         variable,
+                                                         quoting_style);
+    if references == 0
+    {
+        return invalid("should contain iteration variable".into());
+    }
+    if references > 1
+    {
+        return invalid(format!("iteration variable `{variable}` is \
+                                referenced {references} times, but may only \
+                                be compared once"));
+    }
+
+    let mut where_conjuncts = vec![];
+    if let Some(selection) = &select.selection
+    {
+        collect_conjuncts(selection, &mut where_conjuncts);
+    }
+    let mut conditions = vec![(ComparisonLocation::Where, where_conjuncts)];
+    let joins = select.from.first().map_or(&[][..], |from| &from.joins);
+    for (index, join) in joins.iter().enumerate()
+    {
+        if let Some(JoinConstraint::On(condition)) =
+            derive_join_constraint(join)
+        {
+            let mut conjuncts = vec![];
+            collect_conjuncts(condition, &mut conjuncts);
+            conditions.push((ComparisonLocation::Join(index), conjuncts));
+        }
+    }
 
-    let inner_select_table_name = extract_iteration_query_table_name(node)?;
+    let found = conditions.iter().find_map(|(location, conjuncts)| {
+                                     conjuncts.iter()
+                                              .find_map(|conjunct| {
+                                                  get_compared_column(conjunct,
+                                                                      variable)
+                                              })
+                                              .map(|column| (*location, column))
+                                 });
+    let Some((location, column)) = found
+    else
+    {
+        let within_disjunction = conditions.iter()
+                                           .flat_map(|(_, conjuncts)| conjuncts)
+                                           .any(|conjunct| {
+                                               matches!(unnest(conjunct),
+                                   Expr::BinaryOp { op: BinaryOperator::Or,
+                                                    .. })
+                          && count_iteration_variable_references(
+                              &conjunct.to_string(), variable, quoting_style)
+                             > 0
+                                           });
+        return match within_disjunction
+        {
+            true => invalid(format!("iteration variable `{variable}` must \
+                                     not be compared within `OR` \
+                                     conditions")),
+            false => invalid(format!("iteration variable `{variable}` must \
+                                      be compared for equality with a \
+                                      column of the `WHERE` clause or of an \
+                                      `ON` condition, e.g. \
+                                      `a.id = {variable}`")),
+        };
+    };
 
-    let constraint =
-        construct_loop_join_constraint(&loop_target_table_or_alias,
-                                       loop_target_column,
-                                       loop_target_column_name)?;
+    if let ComparisonLocation::Join(index) = location
+    {
+        if !matches!(joins[index].join_operator,
+                     JoinOperator::Join(_)
+                     | JoinOperator::Inner(_)
+                     | JoinOperator::StraightJoin(_))
+        {
+            return invalid(format!("iteration variable `{variable}` must \
+                                    not be compared within the `ON` \
+                                    condition of an outer join"));
+        }
+    }
 
-    let table_factor =
-        construct_loop_join_table_factor(loop_target_table_or_alias,
-                                         inner_select_table_name)?;
+    if !matches!(column, Expr::CompoundIdentifier(_))
+    {
+        return invalid(format!("iteration variable `{variable}` must be \
+                                compared with a qualified column, e.g. \
+                                `a.id = {variable}`"));
+    }
 
-    let join = Join { join_operator: JoinOperator::Left(constraint),
-                      relation: table_factor,
-                      global: false };
-    cte_loop.from[0].joins.insert(0, join);
-    Ok(())
+    Ok(IterationVariableComparison { column,
+                                     location })
 }
 
-fn construct_loop_join_table_factor(
-    loop_target_table_or_alias: Ident,
-    inner_select_table_name: String)
-    -> Result<TableFactor, QueryCompilerError> {
-    let join_alias = loop_target_table_or_alias.value;
-    let table_factor =
-        prepare_parser_with_query(
-            format!("{inner_select_table_name} AS {join_alias}")
-            .as_str())?
-        .parse_table_factor()?;
-    Ok(table_factor)
-}
-
-fn construct_loop_join_constraint(
-    loop_target_table_or_alias: &Ident,
-    loop_target_column: Ident,
-    loop_target_column_name: String)
-    -> Result<JoinConstraint, QueryCompilerError> {
-    let join_subquery =
-        prepare_parser_with_query(
-            format!("SELECT {loop_target_column_name} FROM loop_values
-                     WHERE {loop_target_column_name}
-                           > all_entries.{loop_target_column_name}
-                     LIMIT 1").as_str()
-        )?.parse_query()?;
-    let constraint =
-        JoinConstraint::On(
-            Expr::BinaryOp {
-                left: Box::new(Expr::CompoundIdentifier(vec![
-                    loop_target_table_or_alias.clone(),
-                    loop_target_column,
-                ])),
-                op: BinaryOperator::Eq,
-                right: Box::new(Expr::Subquery(join_subquery)),
-            }
-        );
-    Ok(constraint)
+/// Removes the comparisons with the iteration variables from the chain of
+/// `AND` conditions `condition`. Returns `None` if there are no further
+/// conditions.
+fn remove_comparisons(condition: &Expr,
+                      iteration_variables: &[String])
+                      -> Option<Expr> {
+    let mut conjuncts = vec![];
+    collect_conjuncts(condition, &mut conjuncts);
+    conjuncts.into_iter()
+             .filter(|conjunct| {
+                 iteration_variables.iter().all(|variable| {
+                     get_compared_column(conjunct, variable).is_none()
+                 })
+             })
+             .cloned()
+             .reduce(|left, right| Expr::BinaryOp { left: Box::new(left),
+                                                    op: BinaryOperator::And,
+                                                    right: Box::new(right) })
 }
 
-fn extract_iteration_query_table_name(node: &CompleteCombinedResultNode)
-                                      -> Result<String, QueryCompilerError> {
-    let inner_select =
-        prepare_parser_with_query(&node.inner_query)?.parse_select()?;
-    if inner_select.from.len() != 1
+/// Collects the operands of a chain of `AND` conditions, including those of
+/// parenthesized chains.
+fn collect_conjuncts<'e>(expr: &'e Expr, conjuncts: &mut Vec<&'e Expr>) {
+    match unnest(expr)
     {
-        return Err(QueryCompilerError::InnerQueryInvalid(
-            "expected loop iteration query to have just one table
-             followed by FROM".into()));
+        Expr::BinaryOp { left,
+                         op: BinaryOperator::And,
+                         right, } =>
+        {
+            collect_conjuncts(left, conjuncts);
+            collect_conjuncts(right, conjuncts);
+        },
+        _ => conjuncts.push(expr),
     }
-    let inner_select_table_name = match &inner_select.from[0].relation {
-        TableFactor::Table { name, ..} => Some(name.to_string()),
-        _ => None,
-    }.ok_or(QueryCompilerError::InnerQueryInvalid(
-        "could not derive table name from loop iteration query".into()))?;
-    Ok(inner_select_table_name)
-}
-
-fn extract_table_and_column_for_iteration_variable(
-    cte_loop: &mut Select,
-    iteration_variable: &str)
-    -> Result<(Ident, Ident), QueryCompilerError> {
-    let idents =
-        extract_iteration_variable_idents(cte_loop, iteration_variable)?;
-    if idents.len() != 2
-    {
-        return Err(QueryCompilerError::InnerQueryInvalid(
-            "expected lvalue of iteration variable to be
-             of schema: table.column".into()));
-    }
-    Ok((idents[0].clone(), idents[1].clone()))
-}
-
-fn extract_iteration_variable_idents(
-    cte_loop: &mut Select,
-    iteration_variable: &str)
-    -> Result<Vec<Ident>, QueryCompilerError> {
-    let err_candidate = QueryCompilerError::InnerQueryInvalid(
-        "should contain iteration variable".into());
-    let selection = cte_loop.selection.clone().ok_or(err_candidate.clone())?;
-    let candidate = match &selection
-                    {
-                        Expr::BinaryOp { left: _,
-                                         op: _,
-                                         right: _, } =>
-                        {
-                            get_iteration_target_identifier(&selection,
-                                                            iteration_variable)
-                        },
-                        _ => None,
-                    }.ok_or(err_candidate.clone())?;
-    let idents = match candidate
-                 {
-                     Expr::CompoundIdentifier(idents) => Some(idents),
-                     _ => None,
-                 }.ok_or(err_candidate)?;
-    Ok(idents)
-}
-
-fn get_iteration_target_identifier(expr: &Expr,
-                                   iteration_variable: &str)
-                                   -> Option<Expr> {
-    match expr
+}
+
+/// Returns the column compared with the iteration variable by `condition`,
+/// if it is such a comparison (e.g. `a.id = $id` or `$id = a.id`).
+fn get_compared_column(condition: &Expr,
+                       iteration_variable: &str)
+                       -> Option<Expr> {
+    match unnest(condition)
     {
         Expr::BinaryOp { left,
-                         op,
+                         op: BinaryOperator::Eq,
                          right, } =>
         {
-            if *op == BinaryOperator::Eq
+            if is_iteration_variable(right, iteration_variable)
             {
-                if let Expr::Value(value_with_span) = &**right
-                {
-                    if let Value::Placeholder(var) = &value_with_span.value
-                    {
-                        if var == iteration_variable
-                        {
-                            return Some(*left.clone());
-                        }
-                    }
-                }
+                Some(unnest(left).clone())
+            }
+            else if is_iteration_variable(left, iteration_variable)
+            {
+                Some(unnest(right).clone())
+            }
+            else
+            {
+                None
             }
-            get_iteration_target_identifier(expr, iteration_variable)
         },
         _ => None,
     }
 }
 
-fn transform_loop_table_name_to_cte_alias(cte_loop: &mut Select) {
-    cte_loop.from[0].relation =
-        TableFactor::Table { name: vec!["all_entries".into()].into(),
-                             alias: None,
-                             args: None,
-                             with_hints: vec![],
-                             version: None,
-                             with_ordinality: false,
-                             partitions: vec![],
-                             json_path: None,
-                             sample: None,
-                             index_hints: vec![] };
-}
-
-fn insert_loop_iteration_index(cte_loop: &mut Select)
-                               -> Result<(), QueryCompilerError> {
-    cte_loop.projection.insert(
-        0,
-        SelectItem::UnnamedExpr(
-            prepare_parser_with_query("n + 1")?.parse_expr()?,
-        )
-    );
-    Ok(())
+/// Strips all parentheses enclosing `expr`.
+fn unnest(expr: &Expr) -> &Expr {
+    match expr
+    {
+        Expr::Nested(nested) => unnest(nested),
+        _ => expr,
+    }
+}
+
+/// Counts the references of the iteration `variable` within `query`, except
+/// within string literals and comments.
+fn count_iteration_variable_references(query: &str,
+                                       variable: &str,
+                                       quoting_style: QuotingStyle)
+                                       -> usize {
+    Scanner::new(query).with_quoting_style(quoting_style)
+                       .filter(|token| {
+                           token.kind == TokenKind::Variable
+                           && token.text(query) == variable
+                       })
+                       .count()
+}
+
+/// Whether `expr` is the iteration variable. Dialects knowing `$` within
+/// identifiers (like MySQL) parse it as an identifier, the others as a
+/// placeholder.
+fn is_iteration_variable(expr: &Expr, iteration_variable: &str) -> bool {
+    match unnest(expr)
+    {
+        Expr::Value(ValueWithSpan { value: Value::Placeholder(var),
+                                    .. }) => var == iteration_variable,
+        Expr::Identifier(ident) => ident.value == iteration_variable,
+        _ => false,
+    }
 }
 
-fn prepare_parser_with_query(query: &str)
-                             -> Result<Parser<'_>, QueryCompilerError> {
-    let parser = sqlparser::parser::Parser::new(&GenericDialect {});
+fn prepare_parser_with_query<'a>(query: &'a str,
+                                 dialect: &'a dyn ParserDialect)
+                                 -> Result<Parser<'a>, QueryCompilerError> {
+    let parser = sqlparser::parser::Parser::new(dialect);
     Ok(parser.try_with_sql(query)?)
 }
+
+/// Clauses following the `SELECT` of the inner query, which can't be applied
+/// per iteration of a recursive CTE.
+const UNSUPPORTED_INNER_QUERY_CLAUSES: [Keyword; 4] = [Keyword::ORDER,
+                                                       Keyword::LIMIT,
+                                                       Keyword::OFFSET,
+                                                       Keyword::FETCH];
+
+/// Parses the inner query, which must be a plain `SELECT`.
+fn parse_inner_select(query: &str,
+                      dialect: &dyn ParserDialect)
+                      -> Result<Select, QueryCompilerError> {
+    let mut parser = prepare_parser_with_query(query, dialect)?;
+    let select = parser.parse_select()?;
+    if parser.peek_one_of_keywords(&UNSUPPORTED_INNER_QUERY_CLAUSES)
+             .is_some()
+    {
+        let msg = "inner query must not be ordered or limited (`ORDER BY`, \
+                   `LIMIT`, ...), as this can't be applied per iteration";
+        return Err(QueryCompilerError::inner_query_invalid(msg));
+    }
+    expect_end_of_query(&mut parser)?;
+    Ok(select)
+}
+
+/// Parses `query` as a whole, see `expect_end_of_query`.
+fn parse_complete_query(query: &str,
+                        dialect: &dyn ParserDialect)
+                        -> Result<Box<Query>, QueryCompilerError> {
+    let mut parser = prepare_parser_with_query(query, dialect)?;
+    let parsed = parser.parse_query()?;
+    expect_end_of_query(&mut parser)?;
+    Ok(parsed)
+}
+
+/// The number of columns projected by the query `body`, or `None` if it
+/// can't be told from the query itself, e.g. due to a wildcard.
+fn count_projected_columns(body: &SetExpr) -> Option<usize> {
+    match body
+    {
+        SetExpr::Select(select) =>
+        {
+            let has_wildcard = select.projection.iter().any(is_wildcard);
+            (!has_wildcard).then_some(select.projection.len())
+        },
+        SetExpr::Query(query) => count_projected_columns(&query.body),
+        SetExpr::SetOperation { left, .. } => count_projected_columns(left),
+        SetExpr::Values(values) => values.rows.first().map(Vec::len),
+        _ => None,
+    }
+}
+
+fn is_wildcard(item: &SelectItem) -> bool {
+    matches!(item,
+             SelectItem::Wildcard(..) | SelectItem::QualifiedWildcard(..))
+}
+
+/// Fails unless the parser reached the end of the query (apart from a
+/// terminating `;`), as the parser stops wherever it doesn't know how to
+/// continue. Unsupported trailing syntax would be dropped silently otherwise.
+fn expect_end_of_query(parser: &mut Parser) -> Result<(), QueryCompilerError> {
+    let _ = parser.consume_token(&Token::SemiColon);
+    parser.expect_token(&Token::EOF)?;
+    Ok(())
+}
+
+impl CompleteCombinedResultNode {
+    /// Compiles the directive to a `LATERAL` subquery, i.e. the inner query
+    /// is evaluated once per result of the iteration query:
+    ///
+    /// ```sql
+    /// SELECT all_entries.*
+    /// FROM (SELECT DISTINCT *
+    ///       FROM (iteration query) AS loop_values (loop_value)
+    ///       WHERE loop_value IS NOT NULL) AS loop_values
+    /// CROSS JOIN LATERAL (inner query) AS all_entries
+    /// ```
+    ///
+    /// Like for recursive CTEs, each iteration value is used once and `NULL`
+    /// is skipped. Tuples of iteration variables are mapped to the columns
+    /// `loop_value_1`, `loop_value_2` etc. instead.
+    fn compile_lateral_join(&self,
+                            dialect: &dyn Dialect)
+                            -> Result<String, QueryCompilerError> {
+        let loop_values = self.loop_values_name();
+        let all_entries = self.all_entries_name();
+        let columns = self.loop_value_columns();
+
+        let iteration_query = parse_query(&self.iteration_query, dialect)?;
+        self.check_iteration_projection(&iteration_query)?;
+        let mut inner_query = self.inner_query.clone();
+        for (variable, column) in
+            self.iteration_item_variables.iter().zip(&columns)
+        {
+            inner_query =
+                replace_iteration_variable(&inner_query,
+                                           variable,
+                                           &format!("{loop_values}.{column}"),
+                                           dialect.quoting_style())?;
+        }
+        let inner_query = parse_query(&inner_query, dialect)?;
+
+        let not_null = columns.iter()
+                              .map(|column| format!("{column} IS NOT NULL"))
+                              .collect::<Vec<_>>()
+                              .join(" AND ");
+        let columns = columns.join(", ");
+        Ok(format!("SELECT {all_entries}.* \
+                    FROM (SELECT DISTINCT * \
+                          FROM ({iteration_query}) \
+                          AS {loop_values} ({columns}) \
+                          WHERE {not_null}) AS {loop_values} \
+                    CROSS JOIN LATERAL ({inner_query}) AS {all_entries}"))
+    }
+}
+
+/// Replaces all occurrences of the iteration `variable` within `query`.
+/// Occurrences within string literals or comments are kept as is.
+fn replace_iteration_variable(query: &str,
+                              variable: &str,
+                              replacement: &str,
+                              quoting_style: QuotingStyle)
+                              -> Result<String, QueryCompilerError> {
+    let mut replaced = String::with_capacity(query.len());
+    let mut found = false;
+    for token in Scanner::new(query).with_quoting_style(quoting_style)
+    {
+        let text = token.text(query);
+        if token.kind == TokenKind::Variable && text == variable
+        {
+            replaced.push_str(replacement);
+            found = true;
+        }
+        else
+        {
+            replaced.push_str(text);
+        }
+    }
+
+    match found
+    {
+        true => Ok(replaced),
+        false =>
+        {
+            Err(QueryCompilerError::inner_query_invalid("should contain \
+                                                         iteration variable"))
+        },
+    }
+}
+
+fn parse_query(query: &str,
+               dialect: &dyn Dialect)
+               -> Result<Box<Query>, QueryCompilerError> {
+    // Numbered placeholders are rewritten like within the whole statement,
+    // see `QueryCompiler`.
+    let query = match dialect.numbered_placeholder_style()
+    {
+        PlaceholderStyle::Numeric =>
+        {
+            to_numeric_placeholders(query, dialect.quoting_style())
+        },
+        _ => query.into(),
+    };
+    parse_complete_query(&query, dialect.parser_dialect().as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            compile,
+            parser::nodes::combined_result::*,
+            MySql84,
+            PostgreSql,
+        },
+        sqlparser::dialect::MySqlDialect,
+    };
+
+    fn find_conditions(condition: &str)
+                       -> Result<InnerQueryConditions, QueryCompilerError> {
+        find_conditions_within(&format!("SELECT a.x FROM a WHERE {condition}"),
+                               &["$id"])
+    }
+
+    fn find_conditions_within(
+        inner_query: &str,
+        variables: &[&str])
+        -> Result<InnerQueryConditions, QueryCompilerError> {
+        let node =
+            CompleteCombinedResultNode::new(0,
+                                            0,
+                                            "SELECT id FROM a".into(),
+                                            variables.iter()
+                                                     .map(|variable| {
+                                                         variable.to_string()
+                                                     })
+                                                     .collect(),
+                                            0,
+                                            inner_query.into(),
+                                            0);
+        let select = prepare_parser_with_query(inner_query, &MySqlDialect {})?
+            .parse_select()?;
+        find_inner_query_conditions(&select, &node, QuotingStyle::MySql)
+    }
+
+    #[test]
+    fn iteration_variable_is_found_within_and_chains() {
+        let conditions =
+            find_conditions("a.y = 1 AND (($id) = a.id AND a.z = 2)").unwrap();
+        assert_eq!("a.id", conditions.comparisons[0].column.to_string());
+        assert_eq!(Some("a.y = 1 AND a.z = 2".into()),
+                   conditions.remainder.map(|expr| expr.to_string()));
+
+        let conditions = find_conditions("(a.id = $id)").unwrap();
+        assert_eq!("a.id", conditions.comparisons[0].column.to_string());
+        assert_eq!(None, conditions.remainder);
+    }
+
+    #[test]
+    fn ambiguous_iteration_variables_are_rejected() {
+        for condition in ["a.id = $id OR a.y = 1",
+                          "a.y = 1 AND (a.id = $id OR a.y = 2)",
+                          "a.id = $id AND a.y <> $id",
+                          "a.id > $id",
+                          "a.y = 1"]
+        {
+            let err = find_conditions(condition).unwrap_err();
+            assert!(matches!(err, QueryCompilerError::InnerQueryInvalid(..)),
+                    "{condition}");
+        }
+    }
+
+    #[test]
+    fn iteration_variable_is_found_within_inner_join_conditions() {
+        let conditions =
+            find_conditions_within("SELECT a.x FROM a \
+                                    LEFT JOIN c ON c.a_id = a.id \
+                                    INNER JOIN b ON b.a_id = $id AND b.y = 1 \
+                                    WHERE a.z = 2",
+                                   &["$id"]).unwrap();
+        let comparison = &conditions.comparisons[0];
+        assert_eq!("b.a_id", comparison.column.to_string());
+        assert_eq!(ComparisonLocation::Join(1), comparison.location);
+        assert_eq!(Some("a.z = 2".into()),
+                   conditions.remainder.map(|expr| expr.to_string()));
+
+        let err = find_conditions_within("SELECT a.x FROM a \
+                                          LEFT JOIN b ON b.a_id = $id",
+                                         &["$id"]).unwrap_err();
+        assert!(err.to_string().contains("outer join"), "{err}");
+    }
+
+    #[test]
+    fn each_iteration_variable_of_tuples_is_found() {
+        let conditions =
+            find_conditions_within("SELECT a.x FROM a \
+                                    INNER JOIN b ON b.a_id = a.id \
+                                    AND b.tenant = $tenant \
+                                    WHERE a.y = 1 AND a.id = $id",
+                                   &["$tenant", "$id"]).unwrap();
+        let columns = conditions.comparisons
+                                .iter()
+                                .map(|comparison| comparison.column.to_string())
+                                .collect::<Vec<_>>();
+        assert_eq!(vec!["b.tenant", "a.id"], columns);
+        assert_eq!(Some("a.y = 1".into()),
+                   conditions.remainder.map(|expr| expr.to_string()));
+
+        let err = find_conditions_within("SELECT a.x FROM a WHERE a.id = $id",
+                                         &["$tenant", "$id"]).unwrap_err();
+        assert!(matches!(err, QueryCompilerError::InnerQueryInvalid(..)));
+    }
+
+    #[test]
+    fn ordered_or_limited_inner_queries_are_rejected() {
+        let template = "combined_result (SELECT id FROM a) AS $id { \
+                        SELECT a.id, b.x FROM a \
+                        INNER JOIN b ON b.a_id = a.id WHERE a.id = $id \
+                        ORDER BY b.x LIMIT 3 }";
+
+        let err = compile(template, &MySql84).unwrap_err();
+        assert!(matches!(err, QueryCompilerError::InnerQueryInvalid(..)));
+        assert!(err.to_string().contains("ordered or limited"), "{err}");
+
+        // `LATERAL` joins apply them per iteration, though.
+        assert!(compile(template, &PostgreSql).is_ok());
+    }
+
+    #[test]
+    fn trailing_syntax_of_queries_is_rejected() {
+        let inner_query = "SELECT a.id, b.x FROM a \
+                           INNER JOIN b ON b.a_id = a.id WHERE a.id = $id";
+        for template in [format!("combined_result (SELECT id FROM a) AS $id \
+                                  {{ {inner_query} foo bar baz }}"),
+                         format!("combined_result (SELECT id FROM a foo bar \
+                                  baz) AS $id {{ {inner_query} }}")]
+        {
+            for dialect in [&MySql84 as &dyn Dialect, &PostgreSql]
+            {
+                let err = compile(&template, dialect).unwrap_err();
+                assert!(matches!(err,
+                                 QueryCompilerError::InnerQueryInvalid(..)),
+                        "{dialect}: {err}");
+            }
+        }
+
+        let template = format!("combined_result (SELECT id FROM a) AS $id {{ \
+                                {inner_query}; }}");
+        assert!(compile(&template, &MySql84).is_ok());
+    }
+
+    #[test]
+    fn iteration_projection_must_match_variables() {
+        let template = "SELECT 1; combined_result (SELECT id, name FROM a) \
+                        AS $id { SELECT b.x FROM b WHERE b.a_id = $id }";
+        for dialect in [&MySql84 as &dyn Dialect, &PostgreSql]
+        {
+            let err = compile(template, dialect).unwrap_err();
+            assert!(matches!(err, QueryCompilerError::InnerQueryInvalid(..)),
+                    "{dialect}: {err}");
+            assert_eq!(Span::new(27, 49), err.span(), "{dialect}");
+        }
+
+        let template = "combined_result (SELECT id FROM a) AS ($t, $id) { \
+                        SELECT b.x FROM b WHERE b.t = $t AND b.a_id = $id }";
+        for dialect in [&MySql84 as &dyn Dialect, &PostgreSql]
+        {
+            let err = compile(template, dialect).unwrap_err();
+            assert!(matches!(err, QueryCompilerError::InnerQueryInvalid(..)),
+                    "{dialect}: {err}");
+            assert!(err.to_string().contains("`($t, $id)`"), "{err}");
+        }
+        let template = template.replace("SELECT id", "SELECT t, id");
+        assert!(compile(&template, &MySql84).is_ok());
+
+        // Wildcards can't be checked, so they are left to the database.
+        let template = "combined_result (SELECT * FROM a) AS $id { \
+                        SELECT b.x FROM b WHERE b.a_id = $id }";
+        assert!(compile(template, &MySql84).is_ok());
+    }
+}
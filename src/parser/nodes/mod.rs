@@ -2,8 +2,30 @@ mod combined_result;
 
 pub use combined_result::{CombinedResultNode, CompleteCombinedResultNode};
 
+use crate::lex::Span;
+
 pub trait Node {
     fn get_begin_position(&self) -> usize;
     fn get_scope_begin_position(&self) -> usize;
     fn get_end_position(&self) -> usize;
+
+    /// How many directives enclose this node. Top level nodes have depth 0.
+    fn get_depth(&self) -> usize;
+
+    /// The span of the entire directive within the parsed statement.
+    fn get_span(&self) -> Span {
+        Span::new(self.get_begin_position(), self.get_end_position() + 1)
+    }
+
+    /// Rebuilds the scope of this node from `statement`, while replacing the
+    /// spans of directly nested directives by their generated code.
+    fn embed_nested_code(&mut self,
+                         statement: &str,
+                         nested_code: Vec<(Span, String)>);
+
+    /// Whether `other` is located within the scope of this node.
+    fn encloses(&self, other: &impl Node) -> bool {
+        self.get_scope_begin_position() < other.get_begin_position()
+        && other.get_end_position() < self.get_end_position()
+    }
 }
@@ -5,7 +5,12 @@ use crate::{
         nodes::{CombinedResultNode, CompleteCombinedResultNode},
         types::NodesState,
     },
-    scanner::{get_mandatory_succeeding_character_position, TokenState},
+    scanner::{
+        get_mandatory_succeeding_character_position,
+        get_matching_parenthese_position,
+        Scanner,
+        TokenState,
+    },
 };
 
 /// Reflects the current parser state.
@@ -18,9 +23,10 @@ use crate::{
 /// intermediate state automaton. See `ParserState::finalize()`.
 pub struct ParserState<'t> {
     statement: &'t String,
+    quoting_style: QuotingStyle,
     seen_token_state: Option<TokenState>,
     combined_result_nodes_state: NodesState<CombinedResultNode>,
-    offset: usize,
+    errors: Vec<QueryCompilerError>,
 }
 
 /// The final parser state. See `ParserState::finalize`.
@@ -32,19 +38,48 @@ pub struct FinalParserState {
 impl<'t> ParserState<'t> {
     pub fn initialize(statement: &'t String) -> Self {
         Self { statement,
+               quoting_style: QuotingStyle::default(),
                seen_token_state: None,
                combined_result_nodes_state: NodesState::new(),
-               offset: 0 }
+               errors: vec![] }
+    }
+
+    /// Returns the parser scanning literals, quoted identifiers and comments
+    /// of the given `style`.
+    pub fn with_quoting_style(mut self, style: QuotingStyle) -> Self {
+        self.quoting_style = style;
+        self
     }
 
-    /// Steps through the given statement, word by word, and internally
+    /// Steps through the given statement, token by token, and internally
     /// updates the parser state accordingly (i.e. saves which parsed
     /// objects have been seen and which data they contain).
+    ///
+    /// Errors don't stop the parser. Instead, the directive causing the error
+    /// is skipped and scanning continues, so that all errors of the statement
+    /// are reported at once.
     pub fn parse(&mut self) -> Result<(), QueryCompilerError> {
-        for word in self.statement.split(WORD_DELIMITER)
+        let scanner =
+            Scanner::new(self.statement).with_quoting_style(self.quoting_style);
+        for token in scanner
+        {
+            if let Err(err) = self.advance_token(&token)
+            {
+                self.fail_current_node(err);
+            }
+        }
+
+        // Directives which have not been closed until the end of the
+        // statement are kept, so that `finalize()` reports them as incomplete.
+        while self.combined_result_nodes_state.current_node().is_some()
         {
-            self.advance_word(word)?;
-            self.advance_offset(word);
+            self.combined_result_nodes_state.close_current_node();
+        }
+
+        // Incomplete directives are reported along with the other errors.
+        if !self.errors.is_empty()
+        {
+            self.get_complete_nodes()?;
         }
         Ok(())
     }
@@ -65,6 +100,9 @@ impl<'t> ParserState<'t> {
         Ok(final_state)
     }
 
+    /// Converts all nodes which have not failed while parsing. Errors of
+    /// incomplete nodes are reported together with those raised while
+    /// parsing.
     fn get_complete_nodes(
         &self)
         -> Result<Vec<CompleteCombinedResultNode>, QueryCompilerError> {
@@ -72,6 +110,7 @@ impl<'t> ParserState<'t> {
             self.combined_result_nodes_state
                 .all_nodes
                 .iter()
+                .filter(|n| !n.failed)
                 .map(|n| n.clone().try_into())
                 .collect::<Vec<Result<CompleteCombinedResultNode,
                            QueryCompilerError>>>();
@@ -81,96 +120,56 @@ impl<'t> ParserState<'t> {
                      .filter_map(|el| el.clone().ok())
                      .collect::<Vec<CompleteCombinedResultNode>>();
 
-        let error_variants = converted.iter()
-                                      .filter_map(|el| el.clone().err())
-                                      .collect::<Vec<QueryCompilerError>>();
+        let error_variants =
+            self.errors
+                .iter()
+                .cloned()
+                .chain(converted.iter().filter_map(|el| el.clone().err()))
+                .collect::<Vec<QueryCompilerError>>();
 
         if !error_variants.is_empty()
         {
-            return Err(error_variants[0].clone());
+            return Err(QueryCompilerError::from_errors(error_variants));
         }
 
         Ok(ok_variants)
     }
 
-    fn advance_word(&mut self, word: &str) -> Result<(), QueryCompilerError> {
-        if let Some(next) = self.try_forward_to_keyword_seen_state(word)?
+    /// Records `err` and marks the current node as failed, so that its
+    /// remaining tokens are skipped.
+    fn fail_current_node(&mut self, err: QueryCompilerError) {
+        if let Some(node) = self.combined_result_nodes_state.current_node_mut()
         {
-            self.seen_token_state = Some(next);
-            return Ok(());
+            node.failed = true;
         }
+        self.errors.push(err);
+    }
 
-        if let Some(next) =
-            self.try_forward_to_initiator_char_based_state(word)?
+    fn advance_token(&mut self,
+                     token: &Token)
+                     -> Result<(), QueryCompilerError> {
+        if let Some(next) = TokenState::from_token(token, self.statement)
         {
+            self.handle_transition(&next)?;
             self.seen_token_state = Some(next);
-            return Ok(());
         }
 
         Ok(())
     }
 
-    fn advance_offset(&mut self, word: &str) {
-        self.offset += word.len() + WORD_DELIMITER.len();
-    }
-
-    fn try_forward_to_keyword_seen_state(
-        &mut self,
-        word: &str)
-        -> Result<Option<TokenState>, QueryCompilerError> {
-        if VALID_KEYWORDS.contains(&word)
-        {
-            let current =
-                TokenState::from_keyword(word.to_string(), self.offset)
-                    .expect("checked per .contains() above");
-            self.handle_transition(&current)?;
-            return Ok(Some(current));
-        }
-
-        Ok(None)
-    }
-
-    fn try_forward_to_initiator_char_based_state(
-        &mut self,
-        word: &str)
-        -> Result<Option<TokenState>, QueryCompilerError> {
-        if let Some(initiator) = word.chars().nth(0)
-        {
-            let state_candidate =
-                self.get_scanner_state_by_char_token(initiator);
-
-            if let Some(state) = &state_candidate
-            {
-                self.handle_transition(state)?;
-            }
-
-            return Ok(state_candidate);
-        }
-
-        Ok(None)
-    }
-
-    fn get_scanner_state_by_char_token(&self,
-                                       token: char)
-                                       -> Option<TokenState> {
-        match token
-        {
-            BRACE_START => Some(TokenState::OpeningBrace(self.offset)),
-            BRACE_END => Some(TokenState::ClosingBrace(self.offset)),
-            PARENTHESE_START =>
-            {
-                Some(TokenState::OpeningParenthese(self.offset))
-            },
-            VARIABLE_START => Some(TokenState::Variable(self.offset)),
-            _ => None,
-        }
-    }
-
     fn handle_transition(&mut self,
                          current_token_state: &TokenState)
                          -> Result<(), QueryCompilerError> {
+        let current_node = self.combined_result_nodes_state.current_node();
         let handles_combined_result_node =
-            self.combined_result_nodes_state.current_node.is_some();
+            current_node.is_some_and(|node| !node.failed);
+        let tracks_combined_result_node = current_node.is_some();
+        let handles_inner_query =
+            current_node.is_some_and(|node| node.inner_query_begin.is_some());
+        let handles_variables =
+            current_node.is_some_and(|node| node.iteration_query_end.is_some())
+            && handles_combined_result_node
+            && !handles_inner_query;
 
         match (&self.seen_token_state, current_token_state)
         {
@@ -179,26 +178,36 @@ impl<'t> ParserState<'t> {
                 self.handle_combined_results_keyword(offset)?
             },
 
-            (_, TokenState::OpeningParenthese(offset))
+            (Some(TokenState::CombinedResultsKeyword(_)),
+             TokenState::OpeningParenthese(offset))
                 if handles_combined_result_node =>
             {
                 self.attach_iteration_query(*offset + 1)?
             },
 
-            (_, TokenState::Variable(offset))
-                if handles_combined_result_node =>
+            (_, TokenState::Variable(span))
+                if handles_combined_result_node && !handles_inner_query =>
             {
-                self.attach_variable(*offset)?
+                self.attach_variable(span)?
+            },
+
+            (_, TokenState::OpeningParenthese(offset)) if handles_variables =>
+            {
+                self.mark_tuple_begin(*offset)
+            },
+
+            (_, TokenState::ClosingParenthese(offset)) if handles_variables =>
+            {
+                self.mark_tuple_end(*offset)
             },
 
             (_, TokenState::OpeningBrace(offset))
-                if handles_combined_result_node =>
+                if tracks_combined_result_node && !handles_inner_query =>
             {
                 self.mark_inner_query_begin(*offset)?
             },
 
-            (_, TokenState::ClosingBrace(offset))
-                if handles_combined_result_node =>
+            (_, TokenState::ClosingBrace(offset)) if handles_inner_query =>
             {
                 self.finalize_combined_result_node(offset)
             },
@@ -213,88 +222,138 @@ impl<'t> ParserState<'t> {
     fn handle_combined_results_keyword(&mut self,
                                        offset: &usize)
                                        -> Result<(), QueryCompilerError> {
-        if self.combined_result_nodes_state.current_node.is_some()
+        // Nesting is only possible within the inner query of the enclosing
+        // directive, not within e.g. its iteration query.
+        if self.combined_result_nodes_state
+               .current_node()
+               .and_then(|node| node.iteration_query_end)
+               .is_some_and(|end| *offset < end)
         {
+            let keyword_span =
+                Span::new(*offset, *offset + KEYWORD_COMBINED_RESULT.len());
             let err =
                 QueryCompilerError::UnsupportedNesting(KEYWORD_COMBINED_RESULT,
-                                                       KEYWORD_COMBINED_RESULT);
+                                                       KEYWORD_COMBINED_RESULT,
+                                                       keyword_span);
             return Err(err);
         }
-        self.combined_result_nodes_state.current_node =
-            Some(CombinedResultNode::new(*offset));
+
+        // Otherwise, a directive whose body has not been started yet is
+        // incomplete. It is closed, so that `finalize()` reports it, and
+        // parsing continues with the new directive.
+        if self.combined_result_nodes_state
+               .current_node()
+               .is_some_and(|node| node.inner_query_begin.is_none())
+        {
+            self.combined_result_nodes_state.close_current_node();
+        }
+        let depth = self.combined_result_nodes_state.open_nodes.len();
+        self.combined_result_nodes_state
+            .open_nodes
+            .push(CombinedResultNode::new(*offset, depth));
         Ok(())
     }
 
     fn attach_iteration_query(&mut self,
                               cursor: usize)
                               -> Result<(), QueryCompilerError> {
-        if let Some(node) = &mut self.combined_result_nodes_state.current_node
+        if let Some(node) = self.combined_result_nodes_state.current_node_mut()
         {
-            let brace_start_pos = get_mandatory_succeeding_character_position(
-                cursor,
+            let closing_parenthese_pos =
+                get_matching_parenthese_position(cursor,
+                                                 self.statement,
+                                                 KEYWORD_COMBINED_RESULT,
+                                                 self.quoting_style)?;
+
+            get_mandatory_succeeding_character_position(
+                closing_parenthese_pos + 1,
                 self.statement.len(),
                 self.statement,
                 BRACE_START,
                 KEYWORD_COMBINED_RESULT,
+                self.quoting_style,
             )?;
 
-            let closing_brace_pos =
-                get_mandatory_succeeding_character_position(
-                    cursor,
-                    brace_start_pos,
-                    self.statement,
-                    PARENTHESE_END,
-                    KEYWORD_COMBINED_RESULT,
-                )?;
-
             node.iteration_query =
-                Some(self.statement[cursor .. closing_brace_pos].into());
+                Some(self.statement[cursor .. closing_parenthese_pos].into());
+            node.iteration_query_end = Some(closing_parenthese_pos);
         }
         Ok(())
     }
 
+    /// Attaches an iteration variable following the iteration query, i.e.
+    /// either the single one (`AS $id`) or one of a tuple
+    /// (`AS ($tenant, $id)`).
     fn attach_variable(&mut self,
-                       cursor: usize)
+                       span: &Span)
                        -> Result<(), QueryCompilerError> {
-        let words_beyond_cursor =
-            self.statement[cursor ..].split(WORD_DELIMITER);
-        if let Some(found_variable) = words_beyond_cursor.into_iter().nth(0)
+        if let Some(node) = self.combined_result_nodes_state.current_node_mut()
         {
-            if let Some(node) =
-                &mut self.combined_result_nodes_state.current_node
+            if node.iteration_query_end.is_some_and(|end| span.begin > end)
             {
-                node.iteration_item_variable =
-                    Some(found_variable.trim().into());
+                node.iteration_item_variables
+                    .push(self.statement[span.begin .. span.end].into());
             }
         }
         Ok(())
     }
 
+    /// Marks the opening parenthese of a tuple of iteration variables, i.e.
+    /// one following the iteration query.
+    fn mark_tuple_begin(&mut self, offset: usize) {
+        if let Some(node) = self.combined_result_nodes_state.current_node_mut()
+        {
+            if node.iteration_query_end.is_some_and(|end| offset > end)
+            {
+                node.unclosed_tuple_begin = Some(offset);
+            }
+        }
+    }
+
+    /// Marks the closing parenthese of a tuple of iteration variables.
+    fn mark_tuple_end(&mut self, offset: usize) {
+        if let Some(node) = self.combined_result_nodes_state.current_node_mut()
+        {
+            if node.iteration_query_end.is_some_and(|end| offset > end)
+            {
+                node.unclosed_tuple_begin = None;
+            }
+        }
+    }
+
+    /// Marks the begin of the inner query, after checking that a tuple of
+    /// iteration variables has been closed.
     fn mark_inner_query_begin(&mut self,
                               cursor: usize)
                               -> Result<(), QueryCompilerError> {
-        if let Some(node) = &mut self.combined_result_nodes_state.current_node
+        if let Some(node) = self.combined_result_nodes_state.current_node_mut()
         {
             node.inner_query_begin = Some(cursor);
+
+            if let Some(begin) = node.unclosed_tuple_begin
+            {
+                let err =
+                    QueryCompilerError::UnbalancedParenthese(
+                        KEYWORD_COMBINED_RESULT,
+                        Span::new(begin, begin + 1));
+                return Err(err);
+            }
         }
         Ok(())
     }
 
     fn finalize_combined_result_node(&mut self, offset: &usize) {
-        if let Some(node) = &mut self.combined_result_nodes_state.current_node
+        if let Some(node) = self.combined_result_nodes_state.current_node_mut()
         {
             if let Some(begin) = node.inner_query_begin
             {
                 let slice_start = begin + 1;
-                let slice_end = *offset - 1;
+                let slice_end = *offset;
                 let slice = &self.statement[slice_start .. slice_end];
                 node.inner_query = Some(slice.trim().into());
             }
             node.end_position = Some(*offset);
-            self.combined_result_nodes_state
-                .all_nodes
-                .push(node.clone());
-            self.combined_result_nodes_state.current_node = None;
+            self.combined_result_nodes_state.close_current_node();
         }
     }
 }
@@ -1,11 +1,32 @@
+/// Keeps track of the nodes of a certain kind while parsing.
+///
+/// As nodes may be nested, the nodes which have been opened but not yet
+/// been closed form a stack. The innermost open node is the current one.
 pub struct NodesState<TNode> {
     pub all_nodes: Vec<TNode>,
-    pub current_node: Option<TNode>,
+    pub open_nodes: Vec<TNode>,
 }
 
 impl<TNode> NodesState<TNode> {
     pub fn new() -> Self {
         Self { all_nodes: vec![],
-               current_node: None }
+               open_nodes: vec![] }
+    }
+
+    pub fn current_node(&self) -> Option<&TNode> {
+        self.open_nodes.last()
+    }
+
+    pub fn current_node_mut(&mut self) -> Option<&mut TNode> {
+        self.open_nodes.last_mut()
+    }
+
+    /// Closes the current node, i.e. moves it from the stack of open nodes
+    /// to all (closed) nodes.
+    pub fn close_current_node(&mut self) {
+        if let Some(node) = self.open_nodes.pop()
+        {
+            self.all_nodes.push(node);
+        }
     }
 }
@@ -0,0 +1,179 @@
+//! Python bindings, constructing the module with the fully qualified name
+//! `sqlquerypp.sqlquerypp`. Its interface declaration is located in
+//! `python/sqlquerypp/sqlquerypp.pyi`.
+use {
+    crate::{
+        codegen::get_dialect,
+        compile_script,
+        compile_with_options,
+        error::{ParameterError, QueryCompilerError, SourceLocation},
+        types::{CombinedResultNodeSlice, CompiledQueryDescriptor},
+        CompileOptions,
+        PlaceholderStyle,
+    },
+    pyo3::{
+        create_exception,
+        exceptions::{PyKeyError, PyTypeError, PyValueError},
+        prelude::*,
+    },
+};
+
+create_exception!(sqlquerypp,
+                  PyQueryCompilerError,
+                  PyValueError,
+                  "Raised when compiling a query template fails.");
+
+impl QueryCompilerError {
+    /// Converts the error to a Python exception, whose attributes expose the
+    /// location of the error within `statement`. The individual errors are
+    /// listed in its `errors` attribute.
+    pub fn into_python_exception(self, statement: &str) -> PyErr {
+        let exception = self.to_python_exception(statement);
+        Python::with_gil(|py| {
+            let errors =
+                self.into_errors()
+                    .iter()
+                    .map(|err| {
+                        err.to_python_exception(statement).into_value(py)
+                    })
+                    .collect::<Vec<_>>();
+            exception.value(py).setattr("errors", errors)
+        }).map_or_else(|err| err, |_| exception)
+    }
+
+    fn to_python_exception(&self, statement: &str) -> PyErr {
+        let location = SourceLocation::resolve(statement, self.span().begin);
+        let exception = PyQueryCompilerError::new_err(self.render(statement));
+        Python::with_gil(|py| {
+            let value = exception.value(py);
+            value.setattr("line", location.line)?;
+            value.setattr("column", location.column)?;
+            value.setattr("snippet", self.render(statement))?;
+            value.setattr("help", self.help())?;
+            value.setattr("errors", Vec::<PyObject>::new())
+        }).map_or_else(|err| err, |_| exception)
+    }
+}
+
+impl From<ParameterError> for PyErr {
+    fn from(err: ParameterError) -> Self {
+        match err
+        {
+            ParameterError::MissingParameter(name) => PyKeyError::new_err(name),
+            ParameterError::NotNamed => PyTypeError::new_err(err.to_string()),
+        }
+    }
+}
+
+/// Parses a placeholder style given by name, e.g. `"qmark"`.
+fn parse_placeholder_style(style: &str) -> PyResult<PlaceholderStyle> {
+    style.parse().map_err(PyValueError::new_err)
+}
+
+/// Compiles `statement` to the dialect given by name (e.g. `"mysql84"`),
+/// see `codegen::get_dialect`.
+///
+/// `server_version` (e.g. `"8.0.35"`) declares the version of the target
+/// server, for dialects whose features depend on it. Placeholders of the
+/// resulting query have the style of the template's placeholders, unless an
+/// `output_placeholder_style` is given or the dialect defaults to another
+/// style. The resulting query is only reformatted as a whole if
+/// `pretty_print` is set.
+#[pyfunction]
+#[pyo3(signature = (statement,
+                    dialect,
+                    server_version = None,
+                    placeholder_style = "qmark",
+                    output_placeholder_style = None,
+                    pretty_print = false))]
+fn preprocess_query(statement: String,
+                    dialect: &str,
+                    server_version: Option<&str>,
+                    placeholder_style: &str,
+                    output_placeholder_style: Option<&str>,
+                    pretty_print: bool)
+                    -> PyResult<CompiledQueryDescriptor> {
+    compile_template(&statement,
+                     dialect,
+                     server_version,
+                     placeholder_style,
+                     output_placeholder_style,
+                     pretty_print,
+                     compile_with_options)
+}
+
+/// Compiles each `;`-separated statement of `script`, see
+/// `preprocess_query` and `compile_script`.
+#[pyfunction]
+#[pyo3(signature = (script,
+                    dialect,
+                    server_version = None,
+                    placeholder_style = "qmark",
+                    output_placeholder_style = None,
+                    pretty_print = false))]
+fn preprocess_script(script: String,
+                     dialect: &str,
+                     server_version: Option<&str>,
+                     placeholder_style: &str,
+                     output_placeholder_style: Option<&str>,
+                     pretty_print: bool)
+                     -> PyResult<Vec<CompiledQueryDescriptor>> {
+    compile_template(&script,
+                     dialect,
+                     server_version,
+                     placeholder_style,
+                     output_placeholder_style,
+                     pretty_print,
+                     compile_script)
+}
+
+/// Resolves the compile options given by name and compiles `template` with
+/// `compile`.
+fn compile_template<T, F>(template: &str,
+                          dialect: &str,
+                          server_version: Option<&str>,
+                          placeholder_style: &str,
+                          output_placeholder_style: Option<&str>,
+                          pretty_print: bool,
+                          compile: F)
+                          -> PyResult<T>
+    where F: Fn(&str, &CompileOptions) -> Result<T, QueryCompilerError>
+{
+    let server_version = server_version.map(str::parse)
+                                       .transpose()
+                                       .map_err(PyValueError::new_err)?;
+    let dialect =
+        get_dialect(dialect, server_version).map_err(PyValueError::new_err)?;
+
+    let placeholder_style = parse_placeholder_style(placeholder_style)?;
+    let output_placeholder_style = match output_placeholder_style
+    {
+        Some(style) => parse_placeholder_style(style)?,
+        None => dialect.default_output_placeholder_style()
+                       .unwrap_or(placeholder_style),
+    };
+    let options = CompileOptions { placeholder_style,
+                                   output_placeholder_style,
+                                   pretty_print,
+                                   ..CompileOptions::new(dialect.as_ref()) };
+
+    // Errors are raised as `QueryCompilerError`, pointing at the
+    // location of the error within the template.
+    let raise = |err: QueryCompilerError| err.into_python_exception(template);
+    compile(template, &options).map_err(raise)
+}
+
+/// Constructs the (internal!) sqlquerypp module containing helper
+/// datastructs and compiler implementations.
+#[pymodule]
+fn sqlquerypp(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(preprocess_query, m)?)?;
+    m.add_function(wrap_pyfunction!(preprocess_script, m)?)?;
+
+    m.add_class::<CompiledQueryDescriptor>()?;
+    m.add_class::<CombinedResultNodeSlice>()?;
+    m.add("QueryCompilerError",
+          m.py().get_type::<PyQueryCompilerError>())?;
+
+    Ok(())
+}
@@ -3,10 +3,11 @@ use crate::{error::QueryCompilerError, lex::*};
 /// Reflects choices which token has been seen recently.
 pub enum TokenState {
     OpeningParenthese(usize),
+    ClosingParenthese(usize),
     OpeningBrace(usize),
     ClosingBrace(usize),
     CombinedResultsKeyword(usize),
-    Variable(usize),
+    Variable(Span),
 }
 
 impl TokenState {
@@ -20,6 +21,313 @@ impl TokenState {
             _ => None,
         }
     }
+
+    /// Maps a scanned token to the state it causes, if any. Tokens which are
+    /// irrelevant for `sqlquerypp` directives (whitespace, plain SQL words,
+    /// ...) yield `None`.
+    pub fn from_token(token: &Token, statement: &str) -> Option<Self> {
+        let offset = token.span.begin;
+        match token.kind
+        {
+            TokenKind::Word
+                if VALID_KEYWORDS.contains(&token.text(statement)) =>
+            {
+                Self::from_keyword(token.text(statement).into(), offset)
+            },
+            TokenKind::Variable => Some(TokenState::Variable(token.span)),
+            TokenKind::OpeningParenthese =>
+            {
+                Some(TokenState::OpeningParenthese(offset))
+            },
+            TokenKind::ClosingParenthese =>
+            {
+                Some(TokenState::ClosingParenthese(offset))
+            },
+            TokenKind::OpeningBrace => Some(TokenState::OpeningBrace(offset)),
+            TokenKind::ClosingBrace => Some(TokenState::ClosingBrace(offset)),
+            _ => None,
+        }
+    }
+}
+
+/// Splits a statement into typed tokens.
+///
+/// In contrast to splitting by a delimiter, any kind of whitespace separates
+/// tokens and punctuation (like parentheses and braces) is always emitted as
+/// a token of its own, even if it is directly adjacent to a word.
+///
+/// String literals, quoted identifiers and comments are recognized following
+/// the `QuotingStyle` of the scanner (MySQL's lexical rules, unless
+/// configured otherwise) and emitted as a single token each. Hence, their
+/// contents (e.g. the `$` of a JSON path like `'$.items[0]'`) are never
+/// mistaken for structural characters.
+///
+/// Placeholders are recognized according to the `PlaceholderStyle` of the
+/// scanner, which is `PlaceholderStyle::Qmark` unless configured otherwise.
+/// For the sake of tracing parameters, numbered `?` placeholders (like `?1`)
+/// are recognized as well.
+pub struct Scanner<'t> {
+    statement: &'t str,
+    offset: usize,
+    placeholder_style: PlaceholderStyle,
+    quoting_style: QuotingStyle,
+}
+
+impl<'t> Scanner<'t> {
+    pub fn new(statement: &'t str) -> Self {
+        Self::starting_at(statement, 0)
+    }
+
+    /// Returns a scanner which starts scanning `statement` at byte `offset`.
+    /// The offset must not point into the middle of a token.
+    pub fn starting_at(statement: &'t str, offset: usize) -> Self {
+        Self { statement,
+               offset,
+               placeholder_style: PlaceholderStyle::default(),
+               quoting_style: QuotingStyle::default() }
+    }
+
+    /// Returns the scanner recognizing placeholders of the given `style`.
+    pub fn with_placeholder_style(mut self, style: PlaceholderStyle) -> Self {
+        self.placeholder_style = style;
+        self
+    }
+
+    /// Returns the scanner recognizing literals, quoted identifiers and
+    /// comments of the given `style`.
+    pub fn with_quoting_style(mut self, style: QuotingStyle) -> Self {
+        self.quoting_style = style;
+        self
+    }
+
+    /// Returns the byte offset of the first character at or after `begin`
+    /// which does not fulfill `predicate`.
+    fn end_of_run(&self,
+                  begin: usize,
+                  predicate: impl Fn(char) -> bool)
+                  -> usize {
+        self.statement[begin ..].char_indices()
+                                .find(|(_, c)| !predicate(*c))
+                                .map_or(self.statement.len(), |(i, _)| {
+                                    begin + i
+                                })
+    }
+
+    /// Returns the byte offset right after the closing `quote` of a quoted
+    /// token whose opening quote is located at `begin`.
+    ///
+    /// A doubled quote character does not close the token. If `escapes` is
+    /// set, a backslash escapes the succeeding character. If the closing
+    /// quote is missing, the token extends to the end of the statement.
+    fn end_of_quoted(&self, begin: usize, quote: char, escapes: bool) -> usize {
+        let mut characters =
+            self.statement[begin ..].char_indices().skip(1).peekable();
+        while let Some((i, c)) = characters.next()
+        {
+            if c == ESCAPE_CHARACTER && escapes
+            {
+                characters.next();
+            }
+            else if c == quote
+                      && characters.next_if(|(_, next)| *next == quote)
+                                   .is_none()
+            {
+                return begin + i + c.len_utf8();
+            }
+        }
+        self.statement.len()
+    }
+
+    /// Returns the byte offset of the line break terminating a line comment
+    /// starting at `begin`.
+    fn end_of_line_comment(&self, begin: usize) -> usize {
+        self.end_of_run(begin, |c| c != '\n')
+    }
+
+    /// Returns the byte offset right after the end of a block comment
+    /// starting at `begin`.
+    fn end_of_block_comment(&self, begin: usize) -> usize {
+        let content_begin = begin + BLOCK_COMMENT_START.len();
+        self.statement[content_begin ..].find(BLOCK_COMMENT_END)
+                                        .map_or(self.statement.len(), |i| {
+                                            content_begin
+                                            + i
+                                            + BLOCK_COMMENT_END.len()
+                                        })
+    }
+
+    /// Returns the byte offset right after a placeholder starting at `begin`,
+    /// if there is one.
+    fn end_of_placeholder(&self, begin: usize) -> Option<usize> {
+        let remainder = &self.statement[begin ..];
+        match self.placeholder_style
+        {
+            PlaceholderStyle::Qmark if remainder.starts_with(PLACEHOLDER) =>
+            {
+                let digits_begin = begin + PLACEHOLDER.len_utf8();
+                Some(self.end_of_run(digits_begin, |c| c.is_ascii_digit()))
+            },
+            PlaceholderStyle::Format
+                if remainder.starts_with(FORMAT_PLACEHOLDER) =>
+            {
+                Some(begin + FORMAT_PLACEHOLDER.len())
+            },
+            PlaceholderStyle::Numeric
+                if remainder.starts_with(NUMERIC_PLACEHOLDER_START) =>
+            {
+                self.end_of_numeric_placeholder(begin)
+            },
+            PlaceholderStyle::Named
+                if remainder.starts_with(NAMED_PLACEHOLDER_START) =>
+            {
+                self.end_of_named_placeholder(begin)
+            },
+            PlaceholderStyle::Pyformat
+                if remainder.starts_with(PYFORMAT_PLACEHOLDER_START) =>
+            {
+                self.end_of_pyformat_placeholder(begin)
+            },
+            _ => None,
+        }
+    }
+
+    /// `$` followed by a number, in contrast to variables like `$id`.
+    fn end_of_numeric_placeholder(&self, begin: usize) -> Option<usize> {
+        let digits_begin = begin + NUMERIC_PLACEHOLDER_START.len_utf8();
+        let end = self.end_of_run(digits_begin, is_word_character);
+        let digits = &self.statement[digits_begin .. end];
+        let is_number =
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit());
+        is_number.then_some(end)
+    }
+
+    /// `:` followed by a name. `::` is a cast in some dialects, though.
+    fn end_of_named_placeholder(&self, begin: usize) -> Option<usize> {
+        if self.statement[.. begin].ends_with(NAMED_PLACEHOLDER_START)
+        {
+            return None;
+        }
+        let name_begin = begin + NAMED_PLACEHOLDER_START.len_utf8();
+        let end = self.end_of_run(name_begin, is_word_character);
+        self.statement[name_begin .. end].starts_with(|c: char| {
+                                             c.is_alphabetic() || c == '_'
+                                         })
+                                         .then_some(end)
+    }
+
+    /// `%(` followed by a name and `)s`.
+    fn end_of_pyformat_placeholder(&self, begin: usize) -> Option<usize> {
+        let name_begin = begin + PYFORMAT_PLACEHOLDER_START.len();
+        let name_end = self.end_of_run(name_begin, is_word_character);
+        let is_closed =
+            self.statement[name_end ..].starts_with(PYFORMAT_PLACEHOLDER_END);
+        (name_end > name_begin && is_closed)
+            .then_some(name_end + PYFORMAT_PLACEHOLDER_END.len())
+    }
+
+    /// MySQL only treats `--` as a comment if it is followed by whitespace,
+    /// a control character or the end of the statement.
+    fn starts_line_comment(&self, begin: usize) -> bool {
+        let remainder = &self.statement[begin ..];
+        remainder.starts_with(LINE_COMMENT_START)
+        && (self.quoting_style == QuotingStyle::Standard
+            || remainder[LINE_COMMENT_START.len() ..].chars()
+                                                     .next()
+                                                     .is_none_or(|c| {
+                                                         c.is_whitespace()
+                                                         || c.is_control()
+                                                     }))
+    }
+
+    /// Whether a PostgreSQL string literal with backslash escapes (like
+    /// `E'it\'s'`) starts at `begin`.
+    fn starts_escape_string(&self, begin: usize) -> bool {
+        let remainder = &self.statement[begin ..];
+        self.quoting_style == QuotingStyle::Standard
+        && remainder.get(.. ESCAPE_STRING_PREFIX.len())
+                    .is_some_and(|prefix| {
+                        prefix.eq_ignore_ascii_case(ESCAPE_STRING_PREFIX)
+                    })
+        && remainder[ESCAPE_STRING_PREFIX.len() ..].starts_with(SINGLE_QUOTE)
+        && !self.statement[.. begin].ends_with(is_word_character)
+    }
+}
+
+fn is_word_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+impl Iterator for Scanner<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let begin = self.offset;
+        let current = self.statement[begin ..].chars().next()?;
+        let after_current = begin + current.len_utf8();
+
+        if let Some(end) = self.end_of_placeholder(begin)
+        {
+            self.offset = end;
+            return Some(Token::new(TokenKind::Placeholder, begin, end));
+        }
+
+        let mysql = self.quoting_style == QuotingStyle::MySql;
+        let (kind, end) = match current
+        {
+            _ if self.starts_escape_string(begin) =>
+            {
+                let quote_begin = begin + ESCAPE_STRING_PREFIX.len();
+                (TokenKind::StringLiteral,
+                 self.end_of_quoted(quote_begin, SINGLE_QUOTE, true))
+            },
+            c if c.is_whitespace() => (TokenKind::Whitespace,
+                                       self.end_of_run(begin,
+                                                       char::is_whitespace)),
+            c if is_word_character(c) =>
+            {
+                (TokenKind::Word, self.end_of_run(begin, is_word_character))
+            },
+            VARIABLE_START =>
+            {
+                let end = self.end_of_run(after_current, is_word_character);
+                if end > after_current
+                {
+                    (TokenKind::Variable, end)
+                }
+                else
+                {
+                    (TokenKind::Punctuation, after_current)
+                }
+            },
+            SINGLE_QUOTE => (TokenKind::StringLiteral,
+                             self.end_of_quoted(begin, current, mysql)),
+            DOUBLE_QUOTE if mysql => (TokenKind::StringLiteral,
+                                      self.end_of_quoted(begin, current, true)),
+            DOUBLE_QUOTE | BACKTICK => (TokenKind::QuotedIdentifier,
+                                        self.end_of_quoted(begin, current,
+                                                           false)),
+            HASH_COMMENT_START if mysql =>
+            {
+                (TokenKind::Comment, self.end_of_line_comment(begin))
+            },
+            _ if self.starts_line_comment(begin) =>
+            {
+                (TokenKind::Comment, self.end_of_line_comment(begin))
+            },
+            _ if self.statement[begin ..].starts_with(BLOCK_COMMENT_START) =>
+            {
+                (TokenKind::Comment, self.end_of_block_comment(begin))
+            },
+            PARENTHESE_START => (TokenKind::OpeningParenthese, after_current),
+            PARENTHESE_END => (TokenKind::ClosingParenthese, after_current),
+            BRACE_START => (TokenKind::OpeningBrace, after_current),
+            BRACE_END => (TokenKind::ClosingBrace, after_current),
+            _ => (TokenKind::Punctuation, after_current),
+        };
+
+        self.offset = end;
+        Some(Token::new(kind, begin, end))
+    }
 }
 
 /// Returns the position of a required character.
@@ -33,18 +341,167 @@ impl TokenState {
 ///   should have been closed with '}' (the character)".
 ///
 /// The returned offset is absolute to the entire statement, not just
-/// the scanned slice.
+/// the scanned slice. Occurrences of `character` within string literals,
+/// quoted identifiers and comments are skipped.
 pub fn get_mandatory_succeeding_character_position(
     cursor: usize,
     end: usize,
     statement: &str,
     character: char,
-    keyword: &'static str)
+    keyword: &'static str,
+    quoting_style: QuotingStyle)
+    -> Result<usize, QueryCompilerError> {
+    Scanner::starting_at(&statement[.. end], cursor)
+        .with_quoting_style(quoting_style)
+        .find(|token| token.is_character(statement, character))
+        .map(|token| token.span.begin)
+        .ok_or(QueryCompilerError::MissingCharacter(character,
+                                                    keyword,
+                                                    Span::new(cursor, cursor)))
+}
+
+/// Returns the position of the parenthese closing the one located right
+/// before `cursor`.
+///
+/// Nested parentheses are balanced, while parentheses within string
+/// literals, quoted identifiers and comments are skipped. Scanning stops at
+/// the first structural `{`, because it always starts the body of a
+/// directive. If the opening parenthese is not closed until then, the
+/// returned error points at the innermost parenthese which is still open.
+pub fn get_matching_parenthese_position(
+    cursor: usize,
+    statement: &str,
+    keyword: &'static str,
+    quoting_style: QuotingStyle)
     -> Result<usize, QueryCompilerError> {
-    Ok(cursor
-        + statement[cursor..end]
-            .find(character)
-            .ok_or(
-                QueryCompilerError::MissingCharacter(
-                    character, keyword))?)
+    let mut open_parentheses = vec![cursor - 1];
+    let scanner = Scanner::starting_at(statement, cursor)
+        .with_quoting_style(quoting_style);
+    for token in scanner
+    {
+        match token.kind
+        {
+            TokenKind::OpeningParenthese =>
+            {
+                open_parentheses.push(token.span.begin)
+            },
+            TokenKind::ClosingParenthese =>
+            {
+                open_parentheses.pop();
+                if open_parentheses.is_empty()
+                {
+                    return Ok(token.span.begin);
+                }
+            },
+            TokenKind::OpeningBrace => break,
+            _ =>
+            {},
+        }
+    }
+
+    let unbalanced = *open_parentheses.last()
+                                      .expect("emptiness causes early return");
+    Err(QueryCompilerError::UnbalancedParenthese(keyword,
+                                                 Span::new(unbalanced,
+                                                           unbalanced + 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lex::*, scanner::Scanner};
+
+    fn get_tokens(statement: &str) -> Vec<(TokenKind, &str)> {
+        Scanner::new(statement).map(|token| (token.kind, token.text(statement)))
+                               .collect()
+    }
+
+    #[test]
+    fn no_tokens_from_empty_string() {
+        assert_eq!(0, get_tokens("").len());
+    }
+
+    #[test]
+    fn punctuation_adjacent_to_words() {
+        assert_eq!(vec![(TokenKind::OpeningParenthese, "("),
+                        (TokenKind::Word, "combined_result"),
+                        (TokenKind::Whitespace, "\t"),
+                        (TokenKind::OpeningParenthese, "("),
+                        (TokenKind::Word, "SELECT"),
+                        (TokenKind::Whitespace, "\r\n"),
+                        (TokenKind::Word, "a"),
+                        (TokenKind::ClosingParenthese, ")"),
+                        (TokenKind::Whitespace, " "),
+                        (TokenKind::Variable, "$id"),
+                        (TokenKind::OpeningBrace, "{"),
+                        (TokenKind::Word, "SELECT"),
+                        (TokenKind::Punctuation, "*"),
+                        (TokenKind::ClosingBrace, "}"),],
+                   get_tokens("(combined_result\t(SELECT\r\na) $id{SELECT*}"));
+    }
+
+    #[test]
+    fn lone_dollar_sign_is_punctuation() {
+        assert_eq!(vec![(TokenKind::Punctuation, "$"),
+                        (TokenKind::Whitespace, " "),
+                        (TokenKind::Word, "ä1"),],
+                   get_tokens("$ ä1"));
+    }
+
+    #[test]
+    fn literals_and_comments_are_single_tokens() {
+        assert_eq!(vec![(TokenKind::StringLiteral, "'$.items[0]'"),
+                        (TokenKind::Punctuation, ","),
+                        (TokenKind::StringLiteral, r#""{it''s \" }""#),
+                        (TokenKind::QuotedIdentifier, "`a``{b`"),
+                        (TokenKind::Comment, "-- } $id"),
+                        (TokenKind::Whitespace, "\n"),
+                        (TokenKind::Comment, "# {"),
+                        (TokenKind::Whitespace, "\n"),
+                        (TokenKind::Comment, "/* ( */"),
+                        (TokenKind::Punctuation, "-"),
+                        (TokenKind::Punctuation, "-"),
+                        (TokenKind::Word, "1"),],
+                   get_tokens("'$.items[0]',\"{it''s \\\" }\"`a``{b`-- } $id\n\
+                               # {\n/* ( */--1"));
+    }
+
+    #[test]
+    fn standard_literals_have_no_backslash_escapes() {
+        let statement = "'C:\\'?\"a\\\"E'\\'}'#-1--1\n";
+        let tokens =
+            Scanner::new(statement).with_quoting_style(QuotingStyle::Standard)
+                                   .map(|token| {
+                                       (token.kind, token.text(statement))
+                                   })
+                                   .collect::<Vec<_>>();
+        assert_eq!(vec![(TokenKind::StringLiteral, "'C:\\'"),
+                        (TokenKind::Placeholder, "?"),
+                        (TokenKind::QuotedIdentifier, "\"a\\\""),
+                        (TokenKind::StringLiteral, "E'\\'}'"),
+                        (TokenKind::Punctuation, "#"),
+                        (TokenKind::Punctuation, "-"),
+                        (TokenKind::Word, "1"),
+                        (TokenKind::Comment, "--1"),
+                        (TokenKind::Whitespace, "\n"),],
+                   tokens);
+    }
+
+    #[test]
+    fn placeholders_are_single_tokens() {
+        assert_eq!(vec![(TokenKind::Word, "a"),
+                        (TokenKind::Punctuation, "="),
+                        (TokenKind::Placeholder, "?"),
+                        (TokenKind::Punctuation, ","),
+                        (TokenKind::Placeholder, "?12"),
+                        (TokenKind::Word, "b"),
+                        (TokenKind::StringLiteral, "'?'"),],
+                   get_tokens("a=?,?12b'?'"));
+    }
+
+    #[test]
+    fn unterminated_literal_extends_to_end() {
+        assert_eq!(vec![(TokenKind::Word, "a"),
+                        (TokenKind::StringLiteral, "'b }"),],
+                   get_tokens("a'b }"));
+    }
 }
@@ -0,0 +1,174 @@
+//! Splitting of scripts into their `;`-separated statements, see
+//! `crate::compile_script`.
+use crate::{lex::*, scanner::Scanner};
+
+/// A statement of a script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptStatement {
+    /// The span of the statement within the script, excluding its
+    /// terminating `;`.
+    pub span: Span,
+    /// The number of positional placeholders (`?` and `%s`) within all
+    /// preceding statements. Always 0 for other placeholder styles, as their
+    /// parameters are numbered or named explicitly.
+    pub parameter_offset: usize,
+}
+
+impl ScriptStatement {
+    /// Returns the text of this statement within `script`.
+    pub fn text<'s>(&self, script: &'s str) -> &'s str {
+        &script[self.span.begin .. self.span.end]
+    }
+}
+
+/// Splits `script` at each structural `;`, i.e. skipping those within string
+/// literals, quoted identifiers, comments, parentheses and directive bodies.
+///
+/// Leading and trailing whitespace is trimmed from each statement, while
+/// statements consisting of nothing but whitespace and comments are
+/// dropped.
+pub fn split_script(script: &str,
+                    style: PlaceholderStyle,
+                    quoting_style: QuotingStyle)
+                    -> Vec<ScriptStatement> {
+    let mut statements = vec![];
+    let positional =
+        matches!(style, PlaceholderStyle::Qmark | PlaceholderStyle::Format);
+    let mut depth = 0usize;
+    let mut span: Option<Span> = None;
+    let mut placeholders = 0;
+    let mut parameter_offset = 0;
+    let scanner = Scanner::new(script).with_placeholder_style(style)
+                                      .with_quoting_style(quoting_style);
+    for token in scanner
+    {
+        match token.kind
+        {
+            TokenKind::Whitespace | TokenKind::Comment => continue,
+            TokenKind::OpeningParenthese | TokenKind::OpeningBrace =>
+            {
+                depth += 1
+            },
+            TokenKind::ClosingParenthese | TokenKind::ClosingBrace =>
+            {
+                depth = depth.saturating_sub(1)
+            },
+            TokenKind::Placeholder if positional => placeholders += 1,
+            TokenKind::Punctuation
+                if depth == 0 && token.is_character(script, ';') =>
+            {
+                if let Some(span) = span.take()
+                {
+                    statements.push(ScriptStatement { span,
+                                                      parameter_offset });
+                }
+                parameter_offset = placeholders;
+                continue;
+            },
+            _ =>
+            {},
+        }
+
+        let begin = span.map_or(token.span.begin, |span| span.begin);
+        span = Some(Span::new(begin, token.span.end));
+    }
+
+    if let Some(span) = span
+    {
+        statements.push(ScriptStatement { span,
+                                          parameter_offset });
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compile_script,
+        compile_with_options,
+        error::QueryCompilerError,
+        lex::*,
+        script::split_script,
+        CompileOptions,
+        Sqlite,
+    };
+
+    fn get_statements(script: &str, style: PlaceholderStyle) -> Vec<&str> {
+        split_script(script, style, QuotingStyle::MySql).iter()
+                                   .map(|statement| statement.text(script))
+                                   .collect()
+    }
+
+    #[test]
+    fn scripts_are_split_at_structural_semicolons() {
+        let script = "SELECT ';' AS `a;b`; -- c; d\n\
+                      SELECT 1 /* ; */ FROM x;\n\
+                      combined_result (SELECT id FROM a) AS $id { \
+                      SELECT 2; SELECT 3 };\n\
+                      ; -- trailing comment";
+        assert_eq!(vec!["SELECT ';' AS `a;b`",
+                        "SELECT 1 /* ; */ FROM x",
+                        "combined_result (SELECT id FROM a) AS $id { \
+                         SELECT 2; SELECT 3 }"],
+                   get_statements(script, PlaceholderStyle::Qmark));
+    }
+
+    #[test]
+    fn positional_parameters_are_offset_by_preceding_statements() {
+        let script = "SELECT ?, '?'; SELECT 1; SELECT ?";
+        let statements =
+            split_script(script, PlaceholderStyle::Qmark, QuotingStyle::MySql);
+        let offsets = statements.iter()
+                                .map(|statement| statement.parameter_offset)
+                                .collect::<Vec<_>>();
+        assert_eq!(vec![0, 1, 1], offsets);
+
+        let script = "SELECT $1; SELECT $2";
+        assert!(split_script(script,
+                             PlaceholderStyle::Numeric,
+                             QuotingStyle::MySql).iter()
+                                                 .all(|statement| {
+                                                     statement.parameter_offset
+                                                     == 0
+                                                 }));
+    }
+
+    #[test]
+    fn named_parameters_are_numbered_by_name_within_scripts() {
+        let options = CompileOptions { placeholder_style:
+                                           PlaceholderStyle::Named,
+                                       ..CompileOptions::new(&Sqlite) };
+        let script = "SELECT :a, :b, :a; SELECT :c, :a; SELECT :b";
+        let orders =
+            compile_script(script, &options).unwrap()
+                                            .into_iter()
+                                            .map(|compiled| {
+                                                compiled.parameter_order
+                                            })
+                                            .collect::<Vec<_>>();
+        assert_eq!(vec![vec![0, 1, 0], vec![2, 0], vec![1]], orders);
+    }
+
+    #[test]
+    fn statements_of_scripts_are_compiled_independently() {
+        let options = CompileOptions::new(&Sqlite);
+        let directive = "combined_result (SELECT id FROM a WHERE id > ?) \
+                         AS $id { SELECT a.id, b.x FROM a \
+                         INNER JOIN b ON b.a_id = a.id WHERE a.id = $id }";
+        let script = format!("DELETE FROM a WHERE id = ?;\n{directive};");
+        let compiled = compile_script(&script, &options).unwrap();
+        let standalone = compile_with_options(directive, &options).unwrap();
+        assert_eq!(2, compiled.len());
+        assert!(compiled[0].statement.starts_with("DELETE FROM"));
+        assert_eq!(vec![0], compiled[0].parameter_order);
+        assert!(compiled[1].parameter_order.iter().all(|index| *index == 1));
+        assert_eq!(standalone.statement, compiled[1].statement);
+        assert_eq!(standalone.combined_result_node_slices[0].scope_begin + 28,
+                   compiled[1].combined_result_node_slices[0].scope_begin);
+
+        let script = "SELECT 1; SELECT 2 foo bar";
+        let err = compile_script(script, &options).unwrap_err();
+        assert!(matches!(err, QueryCompilerError::ResultingQueryInvalid(..)));
+        assert_eq!(Span::new(23, 26), err.span());
+    }
+}
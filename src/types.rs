@@ -1,32 +1,125 @@
-//! Datastructs for python bindings.
-use pyo3::{pyclass, pymethods};
+//! Datastructs describing a compiled query. With the `python` feature, they
+//! are exposed to the python bindings as well.
+#[cfg(feature = "python")]
+use pyo3::{pyclass, pymethods, Bound, PyAny, PyResult};
+use {
+    crate::error::ParameterError,
+    std::collections::{BTreeMap, HashMap},
+};
 
-#[pyclass]
-#[derive(Clone)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CombinedResultNodeSlice {
-    #[pyo3(get)]
     pub scope_begin: usize,
-
-    #[pyo3(get)]
     pub scope_end: usize,
 }
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CompiledQueryDescriptor {
-    #[pyo3(get)]
     pub statement: String,
-
-    #[pyo3(get)]
     pub combined_result_node_slices: Vec<CombinedResultNodeSlice>,
+    /// The (zero-based) index of the template parameter which each
+    /// placeholder of `statement` binds, in order of the placeholders.
+    pub parameter_order: Vec<usize>,
+    /// The (zero-based) positions of all placeholders of `statement` by the
+    /// name of the parameter they bind. Empty, unless the placeholders of the
+    /// template are named.
+    pub parameter_positions: BTreeMap<String, Vec<usize>>,
 }
 
+impl CompiledQueryDescriptor {
+    /// Expands `parameters` given by name to the parameters of all
+    /// placeholders of `statement`, in order of the placeholders.
+    pub fn expand_named_parameters<T: Clone>(
+        &self,
+        parameters: &HashMap<String, T>)
+        -> Result<Vec<T>, ParameterError> {
+        if self.parameter_positions.is_empty()
+           && !self.parameter_order.is_empty()
+        {
+            return Err(ParameterError::NotNamed);
+        }
+
+        let mut expanded = vec![None; self.parameter_order.len()];
+        for (name, positions) in self.parameter_positions.iter()
+        {
+            let missing = || ParameterError::MissingParameter(name.clone());
+            let value = parameters.get(name).ok_or_else(missing)?;
+            for position in positions.iter()
+            {
+                expanded[*position] = Some(value.clone());
+            }
+        }
+        // Each placeholder binds a named parameter, so that all of them are
+        // expanded by now.
+        Ok(expanded.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl CompiledQueryDescriptor {
     #[new]
+    #[pyo3(signature = (statement,
+                        combined_result_node_slices,
+                        parameter_order,
+                        parameter_positions = BTreeMap::new()))]
     fn new(statement: String,
-           combined_result_node_slices: Vec<CombinedResultNodeSlice>)
+           combined_result_node_slices: Vec<CombinedResultNodeSlice>,
+           parameter_order: Vec<usize>,
+           parameter_positions: BTreeMap<String, Vec<usize>>)
            -> Self {
         Self { statement,
-               combined_result_node_slices }
+               combined_result_node_slices,
+               parameter_order,
+               parameter_positions }
+    }
+
+    #[pyo3(name = "expand_named_parameters")]
+    fn py_expand_named_parameters<'py>(&self,
+                                       parameters: HashMap<String,
+                                               Bound<'py,
+                                                     PyAny>>)
+                                       -> PyResult<Vec<Bound<'py, PyAny>>> {
+        Ok(self.expand_named_parameters(&parameters)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{error::ParameterError, types::CompiledQueryDescriptor},
+        std::collections::{BTreeMap, HashMap},
+    };
+
+    fn describe(parameter_order: Vec<usize>,
+                parameter_positions: BTreeMap<String, Vec<usize>>)
+                -> CompiledQueryDescriptor {
+        CompiledQueryDescriptor { statement: String::new(),
+                                  combined_result_node_slices: vec![],
+                                  parameter_order,
+                                  parameter_positions }
+    }
+
+    #[test]
+    fn named_parameters_are_expanded_by_position() {
+        let positions =
+            BTreeMap::from([("x".into(), vec![1]), ("y".into(), vec![0, 2])]);
+        let compiled = describe(vec![1, 0, 1], positions);
+        let parameters = HashMap::from([("x".into(), 1), ("y".into(), 2)]);
+        assert_eq!(Ok(vec![2, 1, 2]),
+                   compiled.expand_named_parameters(&parameters));
+
+        let parameters = HashMap::from([("x".into(), 1)]);
+        assert_eq!(Err(ParameterError::MissingParameter("y".into())),
+                   compiled.expand_named_parameters(&parameters));
+    }
+
+    #[test]
+    fn positional_parameters_are_not_expanded() {
+        let compiled = describe(vec![0], BTreeMap::new());
+        assert_eq!(Err(ParameterError::NotNamed),
+                   compiled.expand_named_parameters(&HashMap::<_, i32>::new()));
     }
 }